@@ -10,9 +10,18 @@ pub struct AppConfig {
     pub max_peers_per_torrent: u32,
     pub listen_port: u16,
     pub enable_dht: bool,
+    /// Nodes used to join the DHT on startup; falls back to
+    /// `dht::DEFAULT_BOOTSTRAP_NODES` when empty, so existing config files
+    /// saved before this field existed keep working unchanged.
+    #[serde(default)]
+    pub dht_bootstrap_nodes: Vec<String>,
     pub enable_upnp: bool,
     pub seed_ratio_limit: Option<f32>, // Stop seeding after this ratio
     pub seed_time_limit: Option<u64>, // Stop seeding after this many seconds
+    pub web_ui_enabled: bool,
+    pub web_ui_port: u16,
+    pub web_ui_username: String,
+    pub web_ui_password: String,
 }
 
 impl Default for AppConfig {
@@ -27,9 +36,14 @@ impl Default for AppConfig {
             max_peers_per_torrent: 80,
             listen_port: 6881,
             enable_dht: true,
+            dht_bootstrap_nodes: Vec::new(),
             enable_upnp: true,
             seed_ratio_limit: Some(2.0),
             seed_time_limit: None,
+            web_ui_enabled: false,
+            web_ui_port: 8080,
+            web_ui_username: "admin".to_string(),
+            web_ui_password: "adminadmin".to_string(),
         }
     }
 }
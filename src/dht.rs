@@ -0,0 +1,460 @@
+//! A Kademlia DHT node (BEP 5) used to find peers for magnet links and
+//! trackerless torrents, without depending on any tracker.
+
+use lava_torrent::bencode::BencodeElem;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Nodes kept per k-bucket, per the Kademlia paper.
+const K: usize = 8;
+/// Number of closest unqueried nodes probed per round of an iterative lookup.
+const ALPHA: usize = 3;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Well-known public nodes used to join the DHT on startup.
+pub const DEFAULT_BOOTSTRAP_NODES: &[&str] = &[
+    "router.bittorrent.com:6881",
+    "dht.transmissionbt.com:6881",
+    "router.utorrent.com:6881",
+];
+
+/// A 160-bit node or infohash identifier.
+pub type NodeId = [u8; 20];
+
+/// XOR distance between two 160-bit ids, compared lexicographically
+/// (highest bit first) as the spec requires.
+fn distance(a: &NodeId, b: &NodeId) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    for i in 0..20 {
+        out[i] = a[i] ^ b[i];
+    }
+    out
+}
+
+/// Index of the k-bucket a node with the given distance from us belongs in:
+/// the position of the highest set bit, counting from the least significant
+/// bit of the 160-bit distance (so the top bit is bucket 159).
+fn bucket_index(dist: &[u8; 20]) -> usize {
+    for (byte_index, byte) in dist.iter().enumerate() {
+        if *byte != 0 {
+            let leading = byte.leading_zeros() as usize;
+            return 160 - (byte_index * 8 + leading) - 1;
+        }
+    }
+    0
+}
+
+/// A known remote DHT node.
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+}
+
+/// A Kademlia routing table of k-buckets, keyed by XOR distance from our id.
+pub struct RoutingTable {
+    our_id: NodeId,
+    buckets: Vec<Vec<NodeInfo>>,
+}
+
+impl RoutingTable {
+    fn new(our_id: NodeId) -> Self {
+        Self {
+            our_id,
+            buckets: vec![Vec::new(); 160],
+        }
+    }
+
+    fn insert(&mut self, node: NodeInfo) {
+        if node.id == self.our_id {
+            return;
+        }
+        let bucket = &mut self.buckets[bucket_index(&distance(&self.our_id, &node.id))];
+        if let Some(existing) = bucket.iter().position(|n| n.id == node.id) {
+            bucket.remove(existing);
+        } else if bucket.len() >= K {
+            bucket.remove(0);
+        }
+        bucket.push(node);
+    }
+
+    /// The closest known nodes to `target`, nearest first.
+    fn closest(&self, target: &NodeId, count: usize) -> Vec<NodeInfo> {
+        let mut all: Vec<&NodeInfo> = self.buckets.iter().flatten().collect();
+        all.sort_by_key(|n| distance(target, &n.id));
+        all.into_iter().take(count).cloned().collect()
+    }
+}
+
+/// A Kademlia DHT participant: maintains a routing table and performs
+/// iterative lookups. This node answers with discovered peers but does not
+/// itself respond to queries from other nodes.
+pub struct DhtNode {
+    node_id: NodeId,
+    bootstrap_nodes: Vec<String>,
+    routing_table: tokio::sync::Mutex<RoutingTable>,
+}
+
+impl DhtNode {
+    /// Create a DHT node with a freshly generated random id.
+    pub fn new(bootstrap_nodes: Vec<String>) -> Self {
+        let node_id = generate_node_id();
+        Self {
+            node_id,
+            bootstrap_nodes,
+            routing_table: tokio::sync::Mutex::new(RoutingTable::new(node_id)),
+        }
+    }
+
+    /// Join the DHT by running `find_node` for our own id against every
+    /// configured bootstrap node, seeding the routing table with whatever
+    /// closer nodes they return.
+    pub async fn bootstrap(&self) {
+        for host in &self.bootstrap_nodes {
+            let Ok(mut addrs) = tokio::net::lookup_host(host.as_str()).await else {
+                continue;
+            };
+            let Some(addr) = addrs.next() else { continue };
+            if let Ok(nodes) = self.find_node(addr, &self.node_id).await {
+                let mut table = self.routing_table.lock().await;
+                for node in nodes {
+                    table.insert(node);
+                }
+            }
+        }
+    }
+
+    /// Iteratively search the DHT for peers of `info_hash`, per BEP 5: probe
+    /// the α closest unqueried nodes each round, merge any closer nodes they
+    /// return, and keep going until no closer node is left to query.
+    /// Announces our listening port to every node that returned a token.
+    pub async fn get_peers(&self, info_hash: &NodeId, our_port: u16) -> Vec<SocketAddr> {
+        let mut queried = std::collections::HashSet::new();
+        let mut candidates = self.routing_table.lock().await.closest(info_hash, K);
+        let mut peers = Vec::new();
+        let mut announce_targets: Vec<(SocketAddr, Vec<u8>)> = Vec::new();
+
+        loop {
+            let batch: Vec<NodeInfo> = candidates
+                .iter()
+                .filter(|n| !queried.contains(&n.id))
+                .take(ALPHA)
+                .cloned()
+                .collect();
+            if batch.is_empty() {
+                break;
+            }
+
+            let mut progressed = false;
+            for node in batch {
+                queried.insert(node.id);
+                match self.get_peers_query(node.addr, info_hash).await {
+                    Ok(GetPeersReply::Peers(found, token)) => {
+                        peers.extend(found);
+                        announce_targets.push((node.addr, token));
+                        progressed = true;
+                    }
+                    Ok(GetPeersReply::CloserNodes(nodes)) => {
+                        let mut table = self.routing_table.lock().await;
+                        for n in &nodes {
+                            table.insert(n.clone());
+                        }
+                        drop(table);
+                        for n in nodes {
+                            if !queried.contains(&n.id) && !candidates.iter().any(|c| c.id == n.id)
+                            {
+                                candidates.push(n);
+                                progressed = true;
+                            }
+                        }
+                        candidates.sort_by_key(|n| distance(info_hash, &n.id));
+                    }
+                    Err(_) => {}
+                }
+            }
+            if !progressed {
+                break;
+            }
+        }
+
+        for (addr, token) in announce_targets {
+            let _ = self.announce_peer(addr, info_hash, our_port, &token).await;
+        }
+
+        peers
+    }
+
+    /// Send a `ping` query and return the remote node's id.
+    pub async fn ping(&self, addr: SocketAddr) -> Result<NodeId, String> {
+        let transaction_id = random_transaction_id();
+        let query = krpc_query(&transaction_id, "ping", {
+            let mut args = BTreeMap::new();
+            args.insert("id".to_string(), BencodeElem::Bytes(self.node_id.to_vec()));
+            args
+        });
+
+        let response = send_krpc(addr, &query, &transaction_id).await?;
+        let r = response_dict(&response)?;
+        extract_node_id(&r)
+    }
+
+    /// Send a `find_node` query and return the closest nodes it knows about.
+    pub async fn find_node(&self, addr: SocketAddr, target: &NodeId) -> Result<Vec<NodeInfo>, String> {
+        let transaction_id = random_transaction_id();
+        let query = krpc_query(&transaction_id, "find_node", {
+            let mut args = BTreeMap::new();
+            args.insert("id".to_string(), BencodeElem::Bytes(self.node_id.to_vec()));
+            args.insert("target".to_string(), BencodeElem::Bytes(target.to_vec()));
+            args
+        });
+
+        let response = send_krpc(addr, &query, &transaction_id).await?;
+        let r = response_dict(&response)?;
+        match r.get("nodes") {
+            Some(BencodeElem::Bytes(compact)) => Ok(parse_compact_nodes(compact)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    async fn get_peers_query(
+        &self,
+        addr: SocketAddr,
+        info_hash: &NodeId,
+    ) -> Result<GetPeersReply, String> {
+        let transaction_id = random_transaction_id();
+        let query = krpc_query(&transaction_id, "get_peers", {
+            let mut args = BTreeMap::new();
+            args.insert("id".to_string(), BencodeElem::Bytes(self.node_id.to_vec()));
+            args.insert(
+                "info_hash".to_string(),
+                BencodeElem::Bytes(info_hash.to_vec()),
+            );
+            args
+        });
+
+        let response = send_krpc(addr, &query, &transaction_id).await?;
+        let r = response_dict(&response)?;
+
+        match r.get("values") {
+            Some(_) => {
+                let token = match r.get("token") {
+                    Some(BencodeElem::Bytes(t)) => t.clone(),
+                    _ => Vec::new(),
+                };
+                let peers = match r.get("values") {
+                    Some(BencodeElem::List(items)) => items
+                        .iter()
+                        .filter_map(|item| match item {
+                            BencodeElem::Bytes(compact) if compact.len() == 6 => {
+                                Some(parse_compact_peer(compact))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Ok(GetPeersReply::Peers(peers, token))
+            }
+            None => match r.get("nodes") {
+                Some(BencodeElem::Bytes(compact)) => {
+                    Ok(GetPeersReply::CloserNodes(parse_compact_nodes(compact)))
+                }
+                _ => Ok(GetPeersReply::CloserNodes(Vec::new())),
+            },
+        }
+    }
+
+    /// Announce that we are downloading `info_hash` on `our_port`, using a
+    /// token obtained from a prior `get_peers` reply from this same node.
+    async fn announce_peer(
+        &self,
+        addr: SocketAddr,
+        info_hash: &NodeId,
+        our_port: u16,
+        token: &[u8],
+    ) -> Result<(), String> {
+        let transaction_id = random_transaction_id();
+        let query = krpc_query(&transaction_id, "announce_peer", {
+            let mut args = BTreeMap::new();
+            args.insert("id".to_string(), BencodeElem::Bytes(self.node_id.to_vec()));
+            args.insert(
+                "info_hash".to_string(),
+                BencodeElem::Bytes(info_hash.to_vec()),
+            );
+            args.insert("port".to_string(), BencodeElem::Integer(our_port as i64));
+            args.insert("token".to_string(), BencodeElem::Bytes(token.to_vec()));
+            args.insert("implied_port".to_string(), BencodeElem::Integer(0));
+            args
+        });
+
+        send_krpc(addr, &query, &transaction_id).await?;
+        Ok(())
+    }
+}
+
+enum GetPeersReply {
+    Peers(Vec<SocketAddr>, Vec<u8>),
+    CloserNodes(Vec<NodeInfo>),
+}
+
+fn generate_node_id() -> NodeId {
+    let mut id = [0u8; 20];
+    rand::thread_rng().fill(&mut id);
+    id
+}
+
+fn random_transaction_id() -> Vec<u8> {
+    let mut t = [0u8; 2];
+    rand::thread_rng().fill(&mut t);
+    t.to_vec()
+}
+
+fn krpc_query(transaction_id: &[u8], method: &str, args: BTreeMap<String, BencodeElem>) -> BencodeElem {
+    let mut dict = BTreeMap::new();
+    dict.insert("t".to_string(), BencodeElem::Bytes(transaction_id.to_vec()));
+    dict.insert("y".to_string(), BencodeElem::String("q".to_string()));
+    dict.insert("q".to_string(), BencodeElem::String(method.to_string()));
+    dict.insert("a".to_string(), BencodeElem::Dictionary(args));
+    BencodeElem::Dictionary(dict)
+}
+
+async fn send_krpc(
+    addr: SocketAddr,
+    query: &BencodeElem,
+    transaction_id: &[u8],
+) -> Result<BencodeElem, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    socket.connect(addr).await.map_err(|e| e.to_string())?;
+    socket
+        .send(&query.encode())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut buf = [0u8; 2048];
+    let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| "DHT query timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let parsed = BencodeElem::from_bytes(&buf[..len])
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or("empty DHT response")?;
+
+    let dict = match &parsed {
+        BencodeElem::Dictionary(d) => d,
+        _ => return Err("DHT response was not a dictionary".to_string()),
+    };
+    if let Some(BencodeElem::Bytes(t)) = dict.get("t") {
+        if t != transaction_id {
+            return Err("DHT response transaction id mismatch".to_string());
+        }
+    }
+    if matches!(dict.get("y"), Some(BencodeElem::String(y)) if y == "e") {
+        return Err("DHT node returned an error".to_string());
+    }
+    Ok(parsed)
+}
+
+fn response_dict(response: &BencodeElem) -> Result<&BTreeMap<String, BencodeElem>, String> {
+    let dict = match response {
+        BencodeElem::Dictionary(d) => d,
+        _ => return Err("malformed DHT response".to_string()),
+    };
+    match dict.get("r") {
+        Some(BencodeElem::Dictionary(r)) => Ok(r),
+        _ => Err("DHT response missing 'r'".to_string()),
+    }
+}
+
+fn extract_node_id(r: &BTreeMap<String, BencodeElem>) -> Result<NodeId, String> {
+    match r.get("id") {
+        Some(BencodeElem::Bytes(id)) if id.len() == 20 => {
+            let mut out = [0u8; 20];
+            out.copy_from_slice(id);
+            Ok(out)
+        }
+        _ => Err("DHT response missing node id".to_string()),
+    }
+}
+
+/// Decode a compact node info list: 26 bytes per node (20-byte id + 4-byte
+/// IPv4 + 2-byte port).
+fn parse_compact_nodes(data: &[u8]) -> Vec<NodeInfo> {
+    data.chunks_exact(26)
+        .map(|chunk| {
+            let mut id = [0u8; 20];
+            id.copy_from_slice(&chunk[0..20]);
+            let ip = Ipv4Addr::new(chunk[20], chunk[21], chunk[22], chunk[23]);
+            let port = u16::from_be_bytes([chunk[24], chunk[25]]);
+            NodeInfo {
+                id,
+                addr: SocketAddr::V4(SocketAddrV4::new(ip, port)),
+            }
+        })
+        .collect()
+}
+
+fn parse_compact_peer(data: &[u8]) -> SocketAddr {
+    let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+    let port = u16::from_be_bytes([data[4], data[5]]);
+    SocketAddr::V4(SocketAddrV4::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_to_self_is_zero() {
+        let id: NodeId = [0x42; 20];
+        assert_eq!(distance(&id, &id), [0u8; 20]);
+    }
+
+    #[test]
+    fn bucket_index_of_zero_distance_is_zero() {
+        assert_eq!(bucket_index(&[0u8; 20]), 0);
+    }
+
+    #[test]
+    fn bucket_index_is_the_position_of_the_highest_set_bit() {
+        // A lone bit in the most significant byte's top position is the
+        // farthest possible distance: bucket 159.
+        let mut dist = [0u8; 20];
+        dist[0] = 0b1000_0000;
+        assert_eq!(bucket_index(&dist), 159);
+
+        // A lone bit in the least significant byte's bottom position is the
+        // closest nonzero distance: bucket 0.
+        let mut dist = [0u8; 20];
+        dist[19] = 0b0000_0001;
+        assert_eq!(bucket_index(&dist), 0);
+    }
+
+    #[test]
+    fn bucket_index_picks_the_highest_set_bit_when_several_are_set() {
+        let mut dist = [0u8; 20];
+        dist[10] = 0b0010_0100; // bits at positions 2 and 5 within this byte
+        // Byte 10's top set bit is at leading_zeros() == 2, so the overall
+        // bit position is 160 - (10 * 8 + 2) - 1 = 77.
+        assert_eq!(bucket_index(&dist), 77);
+    }
+
+    #[test]
+    fn parse_compact_nodes_decodes_one_node_per_26_bytes() {
+        let mut data = vec![0xAB; 20];
+        data.extend_from_slice(&[192, 168, 0, 1]);
+        data.extend_from_slice(&6881u16.to_be_bytes());
+
+        let nodes = parse_compact_nodes(&data);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, [0xAB; 20]);
+        assert_eq!(
+            nodes[0].addr,
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 6881))
+        );
+    }
+}
@@ -1,14 +1,60 @@
+mod config;
+mod dht;
+mod metadata;
+mod peer;
+mod picker;
+mod ratelimit;
+mod session;
 mod torrent_engine;
+mod tracker;
+mod webapi;
 
+use cosmic::iced::Subscription;
 use cosmic::prelude::*;
 use cosmic::widget::container;
 use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use torrent_engine::{TorrentEngine, TorrentEvent, TorrentStats};
+
+/// Requests the GUI sends to the background engine; mirrors the subset of
+/// `TorrentEngine`'s API the UI exposes a button for.
+#[derive(Clone, Debug)]
+enum EngineCommand {
+    /// Add a torrent from a magnet link or a `.torrent` file path.
+    Add(String),
+    Pause(String),
+    Resume(String),
+    Remove(String),
+}
+
+/// Sender half of the GUI-to-engine command channel. Set once in `main`;
+/// `CosmicTorrent::update` reads it to forward button presses to the
+/// engine running on the background Tokio runtime.
+static COMMAND_TX: OnceLock<tokio::sync::mpsc::UnboundedSender<EngineCommand>> = OnceLock::new();
+
+/// Receiver half of the engine-to-GUI event channel. Taken once by the
+/// subscription's stream on its first poll; held in a `Mutex` only because
+/// `.subscription()` needs a plain `fn`, not a closure that could capture
+/// the receiver directly.
+static EVENT_RX: Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<TorrentEvent>>> =
+    Mutex::new(None);
 
 fn main() -> cosmic::iced::Result {
     env_logger::init();
+
+    let config = config::AppConfig::load().unwrap_or_default();
+
+    let (command_tx, command_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (ui_event_tx, ui_event_rx) = tokio::sync::mpsc::unbounded_channel();
+    let _ = COMMAND_TX.set(command_tx);
+    *EVENT_RX.lock().unwrap() = Some(ui_event_rx);
+
+    std::thread::spawn(move || run_backend(config, command_rx, ui_event_tx));
+
     cosmic::action::app("Cosmic Torrent")
         .title("Cosmic Torrent")
         .size(800, 600)
+        .subscription(CosmicTorrent::subscription)
         .run(
             CosmicTorrent::new,
             CosmicTorrent::update,
@@ -16,6 +62,86 @@ fn main() -> cosmic::iced::Result {
         )
 }
 
+/// Run the torrent engine's background work — session resume, command
+/// processing, the periodic stats tick, and the optional web API server —
+/// on its own Tokio runtime, alongside the COSMIC event loop on the main
+/// thread. `commands` carries button presses in from the GUI; `ui_events`
+/// carries engine state changes back out to it.
+fn run_backend(
+    config: config::AppConfig,
+    mut commands: tokio::sync::mpsc::UnboundedReceiver<EngineCommand>,
+    ui_events: tokio::sync::mpsc::UnboundedSender<TorrentEvent>,
+) {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(_) => return,
+    };
+
+    runtime.block_on(async move {
+        let (mut engine, mut events) = TorrentEngine::new(&config);
+        let _ = engine.load_session().await;
+
+        let engine = std::sync::Arc::new(tokio::sync::Mutex::new(engine));
+
+        // Forward every engine event straight on to the GUI.
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if ui_events.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                while let Some(command) = commands.recv().await {
+                    let mut engine = engine.lock().await;
+                    match command {
+                        EngineCommand::Add(input) => {
+                            let input = input.trim();
+                            let result = if input.starts_with("magnet:") {
+                                engine.add_magnet(input).await
+                            } else {
+                                engine.add_torrent_file(input).await
+                            };
+                            if let Err(err) = result {
+                                log::warn!("failed to add torrent: {err}");
+                            }
+                        }
+                        EngineCommand::Pause(info_hash) => {
+                            let _ = engine.pause_torrent(&info_hash);
+                        }
+                        EngineCommand::Resume(info_hash) => {
+                            let _ = engine.resume_torrent(&info_hash);
+                        }
+                        EngineCommand::Remove(info_hash) => {
+                            let _ = engine.remove_torrent(&info_hash);
+                        }
+                    }
+                }
+            });
+        }
+
+        {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+                loop {
+                    ticker.tick().await;
+                    engine.lock().await.tick().await;
+                }
+            });
+        }
+
+        if config.web_ui_enabled {
+            let _ = webapi::run(engine, &config).await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    });
+}
+
 /// Represents a torrent with its metadata and status.
 #[derive(Clone, Debug)]
 struct Torrent {
@@ -46,6 +172,9 @@ enum TorrentStatus {
     Error(String),
     /// The torrent download is completed.
     Completed,
+    /// Seeding stopped automatically after reaching the configured seed
+    /// ratio or seed time limit.
+    SeedingLimitReached,
 }
 
 /// The main application state for Cosmic Torrent.
@@ -69,8 +198,8 @@ enum Message {
     ResumeTorrent(String),
     /// Triggered to remove a torrent with the given ID.
     RemoveTorrent(String),
-    /// Triggered on a periodic timer tick to update torrent states.
-    Tick,
+    /// An update pushed from the background torrent engine.
+    EngineEvent(TorrentEvent),
 }
 
 impl CosmicTorrent {
@@ -90,17 +219,9 @@ impl CosmicTorrent {
         match message {
             Message::AddTorrent => {
                 if !self.add_torrent_input.is_empty() {
-                    // Here you would parse the magnet link or .torrent file
-                    let torrent = Torrent {
-                        name: format!("Torrent {}", self.torrents.len() + 1),
-                        size: 1024 * 1024 * 100, // 100MB example
-                        progress: 0.0,
-                        status: TorrentStatus::Downloading,
-                        download_speed: 0,
-                        upload_speed: 0,
-                    };
-                    self.torrents
-                        .insert(self.add_torrent_input.clone(), torrent);
+                    if let Some(tx) = COMMAND_TX.get() {
+                        let _ = tx.send(EngineCommand::Add(self.add_torrent_input.clone()));
+                    }
                     self.add_torrent_input.clear();
                 }
             }
@@ -108,33 +229,106 @@ impl CosmicTorrent {
                 self.add_torrent_input = input;
             }
             Message::PauseTorrent(id) => {
-                if let Some(torrent) = self.torrents.get_mut(&id) {
-                    torrent.status = TorrentStatus::Paused;
+                if let Some(tx) = COMMAND_TX.get() {
+                    let _ = tx.send(EngineCommand::Pause(id));
                 }
             }
             Message::ResumeTorrent(id) => {
-                if let Some(torrent) = self.torrents.get_mut(&id) {
-                    torrent.status = TorrentStatus::Downloading;
+                if let Some(tx) = COMMAND_TX.get() {
+                    let _ = tx.send(EngineCommand::Resume(id));
                 }
             }
             Message::RemoveTorrent(id) => {
-                self.torrents.remove(&id);
+                if let Some(tx) = COMMAND_TX.get() {
+                    let _ = tx.send(EngineCommand::Remove(id));
+                }
             }
-            Message::Tick => {
-                // Update torrent progress and speeds
-                for torrent in self.torrents.values_mut() {
-                    if matches!(torrent.status, TorrentStatus::Downloading) {
-                        torrent.progress = (torrent.progress + 0.01).min(1.0);
-                        torrent.download_speed = (torrent.download_speed + 1024) % (1024 * 100);
-                        if torrent.progress >= 1.0 {
-                            torrent.status = TorrentStatus::Completed;
-                        }
-                    }
+            Message::EngineEvent(event) => self.apply_event(event),
+        }
+    }
+
+    /// Folds one engine event into the torrents shown by the UI.
+    fn apply_event(&mut self, event: TorrentEvent) {
+        match event {
+            TorrentEvent::Added(info_hash, info) => {
+                self.torrents
+                    .entry(info_hash)
+                    .and_modify(|torrent| {
+                        torrent.name = info.name.clone();
+                        torrent.size = info.size;
+                    })
+                    .or_insert_with(|| Torrent {
+                        name: info.name,
+                        size: info.size,
+                        progress: 0.0,
+                        status: TorrentStatus::Downloading,
+                        download_speed: 0,
+                        upload_speed: 0,
+                    });
+            }
+            TorrentEvent::Progress(info_hash, stats) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    self.apply_stats(torrent, &stats);
+                }
+            }
+            TorrentEvent::Completed(info_hash) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    torrent.status = TorrentStatus::Completed;
+                }
+            }
+            TorrentEvent::Error(info_hash, message) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    torrent.status = TorrentStatus::Error(message);
                 }
             }
+            TorrentEvent::Paused(info_hash) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    torrent.status = TorrentStatus::Paused;
+                }
+            }
+            TorrentEvent::Resumed(info_hash) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    torrent.status = if torrent.progress >= 1.0 {
+                        TorrentStatus::Seeding
+                    } else {
+                        TorrentStatus::Downloading
+                    };
+                }
+            }
+            TorrentEvent::SeedingLimitReached(info_hash) => {
+                if let Some(torrent) = self.torrents.get_mut(&info_hash) {
+                    torrent.status = TorrentStatus::SeedingLimitReached;
+                }
+            }
+            TorrentEvent::Removed(info_hash) => {
+                self.torrents.remove(&info_hash);
+            }
         }
     }
 
+    /// Applies a stats update, leaving a `Paused`/`SeedingLimitReached`
+    /// status alone since those aren't derived from progress.
+    fn apply_stats(&self, torrent: &mut Torrent, stats: &TorrentStats) {
+        torrent.progress = stats.progress;
+        torrent.download_speed = stats.download_rate;
+        torrent.upload_speed = stats.upload_rate;
+        if matches!(
+            torrent.status,
+            TorrentStatus::Downloading | TorrentStatus::Seeding
+        ) {
+            torrent.status = if stats.progress >= 1.0 {
+                TorrentStatus::Seeding
+            } else {
+                TorrentStatus::Downloading
+            };
+        }
+    }
+
+    /// Subscribes to events pushed from the background torrent engine.
+    fn subscription(&self) -> Subscription<Message> {
+        Subscription::run(engine_event_stream)
+    }
+
     /// Returns the UI representation of the application state.
     fn view(&self) -> Element<Message> {
         let add_section = row![
@@ -160,6 +354,7 @@ impl CosmicTorrent {
                 TorrentStatus::Paused => "Paused".to_string(),
                 TorrentStatus::Error(err) => format!("Error: {}", err),
                 TorrentStatus::Completed => "Completed".to_string(),
+                TorrentStatus::SeedingLimitReached => "Seeding limit reached".to_string(),
             };
 
             let torrent_row = row![
@@ -200,3 +395,14 @@ impl CosmicTorrent {
             .into()
     }
 }
+
+/// Drains the engine-event receiver handed off from `main` as a `Stream`,
+/// so it can back a COSMIC subscription. The receiver is taken out of
+/// `EVENT_RX` on the first poll and then threaded through `unfold`'s state.
+fn engine_event_stream() -> impl futures::Stream<Item = Message> {
+    futures::stream::unfold(EVENT_RX.lock().unwrap().take(), |state| async move {
+        let mut receiver = state?;
+        let event = receiver.recv().await?;
+        Some((Message::EngineEvent(event), Some(receiver)))
+    })
+}
@@ -0,0 +1,244 @@
+//! Fetching torrent metadata from peers for magnet links, via the
+//! `ut_metadata` extension (BEP 9/10).
+
+use lava_torrent::bencode::BencodeElem;
+use lava_torrent::torrent::v1::Torrent;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+use crate::peer::{self, PeerError, PeerMessage, TorrentShared};
+use crate::torrent_engine::FileInfo;
+
+/// The size of each metadata piece we request, per BEP 9.
+const METADATA_PIECE_SIZE: usize = 16 * 1024;
+/// The extension-message id we advertise for `ut_metadata` in our own
+/// extended handshake; peers echo this id back when sending us data.
+const OUR_UT_METADATA_ID: u8 = 1;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest `metadata_size` we'll accept from a peer's extended handshake.
+/// Real .torrent info dictionaries are at most a few hundred KB even for
+/// huge multi-file torrents; a few MB leaves generous headroom without
+/// trusting an unbounded peer-supplied value.
+const MAX_METADATA_SIZE: usize = 8 * 1024 * 1024;
+
+/// Fetch the info dictionary from a single peer over an already-handshaken
+/// connection, verify it against `info_hash`, and populate `shared` with the
+/// real torrent metadata.
+pub async fn fetch_and_apply(
+    stream: &mut TcpStream,
+    info_hash: &[u8; 20],
+    shared: &Arc<Mutex<TorrentShared>>,
+) -> Result<(), PeerError> {
+    send_extended_handshake(stream).await?;
+    let (remote_ut_metadata_id, metadata_size) = read_extended_handshake(stream).await?;
+
+    let piece_count = metadata_size.div_ceil(METADATA_PIECE_SIZE);
+    let mut buffer = vec![0u8; metadata_size];
+
+    for piece in 0..piece_count {
+        request_metadata_piece(stream, remote_ut_metadata_id, piece as u32).await?;
+        let data = read_metadata_piece(stream, piece as u32).await?;
+        let start = piece * METADATA_PIECE_SIZE;
+        let end = (start + data.len()).min(metadata_size);
+        buffer[start..end].copy_from_slice(&data[..end - start]);
+    }
+
+    let mut hasher = Sha1::new();
+    hasher.update(&buffer);
+    let digest: [u8; 20] = hasher.finalize().into();
+    if &digest != info_hash {
+        return Err(PeerError::MetadataMismatch);
+    }
+
+    apply_metadata(&buffer, shared).await
+}
+
+async fn send_extended_handshake(stream: &mut TcpStream) -> Result<(), PeerError> {
+    let mut supported = BTreeMap::new();
+    supported.insert(
+        "ut_metadata".to_string(),
+        BencodeElem::Integer(OUR_UT_METADATA_ID as i64),
+    );
+    let mut handshake = BTreeMap::new();
+    handshake.insert("m".to_string(), BencodeElem::Dictionary(supported));
+
+    let message = PeerMessage::Extended {
+        id: 0,
+        payload: BencodeElem::Dictionary(handshake).encode(),
+    };
+    stream.write_all(&message.encode()).await?;
+    Ok(())
+}
+
+/// Read messages until the peer's own extended handshake arrives, returning
+/// the extension id it wants `ut_metadata` requests sent to and the total
+/// metadata size it advertised.
+async fn read_extended_handshake(stream: &mut TcpStream) -> Result<(u8, usize), PeerError> {
+    loop {
+        let message = tokio::time::timeout(REQUEST_TIMEOUT, peer::read_message(stream))
+            .await
+            .map_err(|_| PeerError::ConnectionClosed)??;
+
+        if let PeerMessage::Extended { id: 0, payload } = message {
+            let dict = decode_dict(&payload).ok_or(PeerError::ConnectionClosed)?;
+            let remote_id = match dict.get("m").and_then(|m| match m {
+                BencodeElem::Dictionary(m) => m.get("ut_metadata"),
+                _ => None,
+            }) {
+                Some(BencodeElem::Integer(id)) => *id as u8,
+                _ => return Err(PeerError::ConnectionClosed),
+            };
+            let metadata_size = match dict.get("metadata_size") {
+                Some(BencodeElem::Integer(size)) if *size >= 0 => *size as usize,
+                _ => return Err(PeerError::ConnectionClosed),
+            };
+            if metadata_size > MAX_METADATA_SIZE {
+                return Err(PeerError::ConnectionClosed);
+            }
+            return Ok((remote_id, metadata_size));
+        }
+        // Bitfield/Have/choke messages may arrive first; keep waiting.
+    }
+}
+
+async fn request_metadata_piece(
+    stream: &mut TcpStream,
+    remote_ut_metadata_id: u8,
+    piece: u32,
+) -> Result<(), PeerError> {
+    let mut request = BTreeMap::new();
+    request.insert("msg_type".to_string(), BencodeElem::Integer(0));
+    request.insert("piece".to_string(), BencodeElem::Integer(piece as i64));
+
+    let message = PeerMessage::Extended {
+        id: remote_ut_metadata_id,
+        payload: BencodeElem::Dictionary(request).encode(),
+    };
+    stream.write_all(&message.encode()).await?;
+    Ok(())
+}
+
+async fn read_metadata_piece(stream: &mut TcpStream, expected_piece: u32) -> Result<Vec<u8>, PeerError> {
+    loop {
+        let message = tokio::time::timeout(REQUEST_TIMEOUT, peer::read_message(stream))
+            .await
+            .map_err(|_| PeerError::ConnectionClosed)??;
+
+        if let PeerMessage::Extended { id: OUR_UT_METADATA_ID, payload } = message {
+            let split = bencode_dict_end(&payload).ok_or(PeerError::ConnectionClosed)?;
+            let dict = decode_dict(&payload[..split]).ok_or(PeerError::ConnectionClosed)?;
+
+            let msg_type = match dict.get("msg_type") {
+                Some(BencodeElem::Integer(t)) => *t,
+                _ => return Err(PeerError::ConnectionClosed),
+            };
+            let piece = match dict.get("piece") {
+                Some(BencodeElem::Integer(p)) => *p as u32,
+                _ => return Err(PeerError::ConnectionClosed),
+            };
+
+            match msg_type {
+                1 if piece == expected_piece => return Ok(payload[split..].to_vec()),
+                2 => return Err(PeerError::ConnectionClosed),
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Decode a single bencoded dictionary from a byte slice.
+fn decode_dict(bytes: &[u8]) -> Option<BTreeMap<String, BencodeElem>> {
+    match BencodeElem::from_bytes(bytes).ok()?.into_iter().next()? {
+        BencodeElem::Dictionary(d) => Some(d),
+        _ => None,
+    }
+}
+
+/// Find the end offset of the first bencoded dictionary in `buf`, so the
+/// raw metadata block appended after it (per BEP 9) can be sliced off.
+fn bencode_dict_end(buf: &[u8]) -> Option<usize> {
+    if buf.first() != Some(&b'd') {
+        return None;
+    }
+    let mut depth = 0i32;
+    let mut i = 0usize;
+    while i < buf.len() {
+        match buf[i] {
+            b'd' | b'l' => {
+                depth += 1;
+                i += 1;
+            }
+            b'e' => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            b'i' => {
+                let end = buf[i..].iter().position(|b| *b == b'e')? + i;
+                i = end + 1;
+            }
+            b'0'..=b'9' => {
+                let colon = buf[i..].iter().position(|b| *b == b':')? + i;
+                let len: usize = std::str::from_utf8(&buf[i..colon]).ok()?.parse().ok()?;
+                i = colon + 1 + len;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Parse the verified info dictionary and write its fields into `shared`.
+async fn apply_metadata(
+    info_dict_bytes: &[u8],
+    shared: &Arc<Mutex<TorrentShared>>,
+) -> Result<(), PeerError> {
+    let mut wrapped = b"d4:info".to_vec();
+    wrapped.extend_from_slice(info_dict_bytes);
+    wrapped.push(b'e');
+
+    let torrent =
+        Torrent::read_from_bytes(&wrapped).map_err(|_| PeerError::MetadataMismatch)?;
+
+    let files: Vec<FileInfo> = if let Some(files) = torrent.files.as_ref() {
+        files
+            .iter()
+            .map(|file| FileInfo {
+                path: std::path::PathBuf::from(&file.path.join("/")),
+                size: file.length as u64,
+            })
+            .collect()
+    } else {
+        vec![FileInfo {
+            path: std::path::PathBuf::from(&torrent.name),
+            size: torrent.length as u64,
+        }]
+    };
+    let total_size = files.iter().map(|f| f.size).sum();
+    let piece_hashes: Vec<[u8; 20]> = torrent
+        .pieces
+        .iter()
+        .map(|piece| {
+            let mut hash = [0u8; 20];
+            hash.copy_from_slice(piece);
+            hash
+        })
+        .collect();
+
+    let mut state = shared.lock().await;
+    state.name = torrent.name.clone();
+    state.piece_length = torrent.piece_length as u64;
+    state.total_size = total_size;
+    state.completed_pieces = vec![false; piece_hashes.len()];
+    state.picker = crate::picker::PiecePicker::new(piece_hashes.len());
+    state.piece_hashes = piece_hashes;
+    state.files = files;
+    Ok(())
+}
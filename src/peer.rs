@@ -0,0 +1,789 @@
+//! The BitTorrent peer wire protocol: handshakes, message framing, and the
+//! per-peer block download loop.
+
+use sha1::{Digest, Sha1};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::picker::PiecePicker;
+use crate::ratelimit::RateLimiter;
+use crate::torrent_engine::FileInfo;
+
+/// Length in bytes of the fixed-size handshake message.
+pub const HANDSHAKE_LEN: usize = 68;
+/// The protocol string sent in every handshake.
+pub const PROTOCOL: &[u8] = b"BitTorrent protocol";
+/// Size of a single requested block.
+pub const BLOCK_SIZE: u32 = 16 * 1024;
+/// How many blocks we keep outstanding at once per peer.
+const PIPELINE_DEPTH: usize = 5;
+/// Largest wire message we'll allocate a buffer for. A `Piece` message is
+/// the biggest legitimate message (a block plus a small header), so this
+/// leaves generous headroom over `BLOCK_SIZE` without trusting a peer's
+/// claimed length unboundedly.
+const MAX_MESSAGE_LEN: usize = BLOCK_SIZE as usize + 1024;
+
+/// Peer wire message IDs, as defined by the BitTorrent spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeerMessage {
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    Have(u32),
+    Bitfield(Vec<u8>),
+    Request { index: u32, begin: u32, length: u32 },
+    Piece { index: u32, begin: u32, block: Vec<u8> },
+    Cancel { index: u32, begin: u32, length: u32 },
+    /// A BEP 10 extension-protocol message: a sub-protocol id (0 is always
+    /// the extended handshake) and its raw payload.
+    Extended { id: u8, payload: Vec<u8> },
+}
+
+impl PeerMessage {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        match self {
+            PeerMessage::KeepAlive => return 0u32.to_be_bytes().to_vec(),
+            PeerMessage::Choke => body.push(0),
+            PeerMessage::Unchoke => body.push(1),
+            PeerMessage::Interested => body.push(2),
+            PeerMessage::NotInterested => body.push(3),
+            PeerMessage::Have(index) => {
+                body.push(4);
+                body.extend_from_slice(&index.to_be_bytes());
+            }
+            PeerMessage::Bitfield(bits) => {
+                body.push(5);
+                body.extend_from_slice(bits);
+            }
+            PeerMessage::Request { index, begin, length } => {
+                body.push(6);
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&begin.to_be_bytes());
+                body.extend_from_slice(&length.to_be_bytes());
+            }
+            PeerMessage::Piece { index, begin, block } => {
+                body.push(7);
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&begin.to_be_bytes());
+                body.extend_from_slice(block);
+            }
+            PeerMessage::Cancel { index, begin, length } => {
+                body.push(8);
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&begin.to_be_bytes());
+                body.extend_from_slice(&length.to_be_bytes());
+            }
+            PeerMessage::Extended { id, payload } => {
+                body.push(20);
+                body.push(*id);
+                body.extend_from_slice(payload);
+            }
+        }
+        let mut out = (body.len() as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(&body);
+        out
+    }
+
+    pub(crate) fn decode(body: &[u8]) -> Option<PeerMessage> {
+        if body.is_empty() {
+            return Some(PeerMessage::KeepAlive);
+        }
+        let rest = &body[1..];
+        match body[0] {
+            0 => Some(PeerMessage::Choke),
+            1 => Some(PeerMessage::Unchoke),
+            2 => Some(PeerMessage::Interested),
+            3 => Some(PeerMessage::NotInterested),
+            4 if rest.len() >= 4 => Some(PeerMessage::Have(u32::from_be_bytes(
+                rest[0..4].try_into().ok()?,
+            ))),
+            5 => Some(PeerMessage::Bitfield(rest.to_vec())),
+            6 if rest.len() >= 12 => Some(PeerMessage::Request {
+                index: u32::from_be_bytes(rest[0..4].try_into().ok()?),
+                begin: u32::from_be_bytes(rest[4..8].try_into().ok()?),
+                length: u32::from_be_bytes(rest[8..12].try_into().ok()?),
+            }),
+            7 if rest.len() >= 8 => Some(PeerMessage::Piece {
+                index: u32::from_be_bytes(rest[0..4].try_into().ok()?),
+                begin: u32::from_be_bytes(rest[4..8].try_into().ok()?),
+                block: rest[8..].to_vec(),
+            }),
+            8 if rest.len() >= 12 => Some(PeerMessage::Cancel {
+                index: u32::from_be_bytes(rest[0..4].try_into().ok()?),
+                begin: u32::from_be_bytes(rest[4..8].try_into().ok()?),
+                length: u32::from_be_bytes(rest[8..12].try_into().ok()?),
+            }),
+            20 if !rest.is_empty() => Some(PeerMessage::Extended {
+                id: rest[0],
+                payload: rest[1..].to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Shared, mutable state for a single torrent that every connected peer task
+/// reads from and writes into.
+pub struct TorrentShared {
+    /// The info hash (hex-encoded) of the torrent.
+    pub info_hash: String,
+    /// The display name of the torrent, filled in once metadata is known.
+    pub name: String,
+    /// SHA-1 hash of each piece, in order.
+    pub piece_hashes: Vec<[u8; 20]>,
+    /// Length in bytes of a full piece (the last piece may be shorter).
+    pub piece_length: u64,
+    /// Total size of the torrent across all files.
+    pub total_size: u64,
+    /// Files that make up the torrent, in order.
+    pub files: Vec<FileInfo>,
+    /// Root directory all torrents are downloaded under; files are written
+    /// to `base_download_path/<torrent name>/...`.
+    pub base_download_path: PathBuf,
+    /// Which pieces have been downloaded and verified.
+    pub completed_pieces: Vec<bool>,
+    /// Total verified bytes downloaded so far.
+    pub downloaded: u64,
+    /// Total bytes uploaded so far.
+    pub uploaded: u64,
+    /// Number of currently connected peers.
+    pub peers: u32,
+    /// Number of connected peers that report having the full torrent.
+    pub seeds: u32,
+    /// Rarest-first piece selection and endgame bookkeeping, shared across
+    /// every peer task for this torrent.
+    pub picker: PiecePicker,
+}
+
+impl TorrentShared {
+    pub fn piece_count(&self) -> usize {
+        self.piece_hashes.len()
+    }
+
+    pub fn piece_size(&self, index: u32) -> u32 {
+        let start = index as u64 * self.piece_length;
+        let remaining = self.total_size.saturating_sub(start);
+        remaining.min(self.piece_length) as u32
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.total_size == 0 {
+            return 0.0;
+        }
+        self.downloaded as f32 / self.total_size as f32
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.completed_pieces.is_empty() && self.completed_pieces.iter().all(|done| *done)
+    }
+}
+
+/// Errors that can occur while talking to a peer.
+#[derive(Debug)]
+pub enum PeerError {
+    Io(io::Error),
+    HandshakeMismatch,
+    ConnectionClosed,
+    /// The reassembled `ut_metadata` info dictionary didn't match the
+    /// magnet's infohash, or couldn't be parsed as one.
+    MetadataMismatch,
+    /// A peer sent a wire message whose declared length exceeded
+    /// `MAX_MESSAGE_LEN`; the connection is dropped rather than allocating
+    /// a buffer of that size.
+    MessageTooLarge(usize),
+}
+
+impl From<io::Error> for PeerError {
+    fn from(e: io::Error) -> Self {
+        PeerError::Io(e)
+    }
+}
+
+impl std::fmt::Display for PeerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeerError::Io(e) => write!(f, "peer io error: {}", e),
+            PeerError::HandshakeMismatch => write!(f, "peer sent a mismatched info hash"),
+            PeerError::ConnectionClosed => write!(f, "peer closed the connection"),
+            PeerError::MetadataMismatch => {
+                write!(f, "peer's metadata did not match the expected infohash")
+            }
+            PeerError::MessageTooLarge(len) => {
+                write!(f, "peer sent an oversized message ({len} bytes)")
+            }
+        }
+    }
+}
+
+/// Outcome of a successful handshake.
+pub struct Handshake {
+    /// The remote peer's 20-byte peer id.
+    pub peer_id: [u8; 20],
+    /// Whether the remote peer advertised BEP 10 extension-protocol support.
+    pub supports_extensions: bool,
+}
+
+/// The reserved-byte bit (BEP 10) that advertises extension-protocol support.
+const EXTENSION_PROTOCOL_BIT: u8 = 0x10;
+
+/// Perform the 68-byte BitTorrent handshake over an established TCP stream,
+/// verifying that the remote peer's info hash matches ours and advertising
+/// our own support for the BEP 10 extension protocol.
+pub async fn perform_handshake(
+    stream: &mut TcpStream,
+    info_hash: &[u8; 20],
+    our_peer_id: &[u8; 20],
+) -> Result<Handshake, PeerError> {
+    let mut reserved = [0u8; 8];
+    reserved[5] |= EXTENSION_PROTOCOL_BIT;
+
+    let mut outgoing = Vec::with_capacity(HANDSHAKE_LEN);
+    outgoing.push(PROTOCOL.len() as u8);
+    outgoing.extend_from_slice(PROTOCOL);
+    outgoing.extend_from_slice(&reserved);
+    outgoing.extend_from_slice(info_hash);
+    outgoing.extend_from_slice(our_peer_id);
+    stream.write_all(&outgoing).await?;
+
+    let mut incoming = [0u8; HANDSHAKE_LEN];
+    stream.read_exact(&mut incoming).await?;
+
+    let remote_hash = &incoming[28..48];
+    if remote_hash != info_hash {
+        return Err(PeerError::HandshakeMismatch);
+    }
+
+    let mut remote_peer_id = [0u8; 20];
+    remote_peer_id.copy_from_slice(&incoming[48..68]);
+    Ok(Handshake {
+        peer_id: remote_peer_id,
+        supports_extensions: incoming[25] & EXTENSION_PROTOCOL_BIT != 0,
+    })
+}
+
+pub(crate) async fn read_message(stream: &mut TcpStream) -> Result<PeerMessage, PeerError> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len == 0 {
+        return Ok(PeerMessage::KeepAlive);
+    }
+    if len > MAX_MESSAGE_LEN {
+        return Err(PeerError::MessageTooLarge(len));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+    PeerMessage::decode(&body).ok_or(PeerError::ConnectionClosed)
+}
+
+/// Where a slice of piece data lands within a single on-disk file: the
+/// file's path, the byte offset to seek to, and the `[start, start+len)`
+/// range of the piece's data that belongs there.
+pub(crate) struct FileRange {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub data_start: usize,
+    pub data_len: usize,
+}
+
+/// Map a piece's byte range onto the one or more files it spans, in the
+/// order needed to read or write it. `piece_offset` is the byte offset
+/// within the piece to start at (0 for a whole piece, or a block's
+/// `begin` when only part of the piece is being read).
+pub(crate) fn piece_file_ranges(
+    files: &[FileInfo],
+    piece_length: u64,
+    piece_index: u32,
+    piece_offset: u64,
+    len: usize,
+) -> Vec<FileRange> {
+    let mut ranges = Vec::new();
+    let mut global_offset = piece_index as u64 * piece_length + piece_offset;
+    let mut remaining = len;
+    let mut file_start: u64 = 0;
+
+    for file in files {
+        let file_end = file_start + file.size;
+        if global_offset < file_end && remaining > 0 {
+            let offset = global_offset - file_start;
+            let chunk = ((file.size - offset).min(remaining as u64)) as usize;
+            ranges.push(FileRange {
+                path: file.path.clone(),
+                offset,
+                data_start: len - remaining,
+                data_len: chunk,
+            });
+            global_offset += chunk as u64;
+            remaining -= chunk;
+        }
+        file_start = file_end;
+        if remaining == 0 {
+            break;
+        }
+    }
+    ranges
+}
+
+/// Write a verified, fully-assembled piece to the correct location across
+/// one or more of the torrent's files.
+fn write_piece(
+    files: &[FileInfo],
+    piece_length: u64,
+    piece_index: u32,
+    data: &[u8],
+    download_path: &Path,
+) -> io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+
+    for range in piece_file_ranges(files, piece_length, piece_index, 0, data.len()) {
+        let full_path = download_path.join(&range.path);
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut handle = OpenOptions::new().create(true).write(true).open(&full_path)?;
+        handle.seek(SeekFrom::Start(range.offset))?;
+        handle.write_all(&data[range.data_start..range.data_start + range.data_len])?;
+    }
+    Ok(())
+}
+
+/// Read back a single requested block to serve a peer's `Request`, the
+/// mirror of [`write_piece`].
+fn read_block(
+    files: &[FileInfo],
+    piece_length: u64,
+    piece_index: u32,
+    begin: u32,
+    length: u32,
+    download_path: &Path,
+) -> io::Result<Vec<u8>> {
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut buffer = vec![0u8; length as usize];
+    for range in piece_file_ranges(files, piece_length, piece_index, begin as u64, length as usize)
+    {
+        let full_path = download_path.join(&range.path);
+        let mut handle = File::open(&full_path)?;
+        handle.seek(SeekFrom::Start(range.offset))?;
+        handle.read_exact(&mut buffer[range.data_start..range.data_start + range.data_len])?;
+    }
+    Ok(buffer)
+}
+
+/// Connect to a single peer, perform the handshake, and drive the block
+/// download loop for as long as the connection stays open.
+pub async fn run_peer_session(
+    addr: std::net::SocketAddr,
+    info_hash: [u8; 20],
+    our_peer_id: [u8; 20],
+    shared: Arc<Mutex<TorrentShared>>,
+    upload_limiter: Arc<RateLimiter>,
+    download_limiter: Arc<RateLimiter>,
+) -> Result<(), PeerError> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let handshake = perform_handshake(&mut stream, &info_hash, &our_peer_id).await?;
+
+    {
+        let mut state = shared.lock().await;
+        state.peers += 1;
+    }
+
+    let needs_metadata = shared.lock().await.piece_hashes.is_empty();
+    if needs_metadata {
+        if !handshake.supports_extensions {
+            let mut state = shared.lock().await;
+            state.peers = state.peers.saturating_sub(1);
+            return Err(PeerError::ConnectionClosed);
+        }
+        if let Err(e) = crate::metadata::fetch_and_apply(&mut stream, &info_hash, &shared).await {
+            let mut state = shared.lock().await;
+            state.peers = state.peers.saturating_sub(1);
+            return Err(e);
+        }
+    }
+
+    let result =
+        peer_session_loop(&mut stream, &shared, &upload_limiter, &download_limiter).await;
+    {
+        let mut state = shared.lock().await;
+        state.peers = state.peers.saturating_sub(1);
+    }
+    result
+}
+
+async fn peer_session_loop(
+    stream: &mut TcpStream,
+    shared: &Arc<Mutex<TorrentShared>>,
+    upload_limiter: &Arc<RateLimiter>,
+    download_limiter: &Arc<RateLimiter>,
+) -> Result<(), PeerError> {
+    let mut peer_has: Vec<bool> = Vec::new();
+    let mut current_piece: Option<(u32, Vec<u8>, u32)> = None;
+    let mut counted_as_seed = false;
+
+    let result = peer_session_body(
+        stream,
+        shared,
+        upload_limiter,
+        download_limiter,
+        &mut peer_has,
+        &mut current_piece,
+        &mut counted_as_seed,
+    )
+    .await;
+
+    if counted_as_seed {
+        let mut state = shared.lock().await;
+        state.seeds = state.seeds.saturating_sub(1);
+    }
+    if let Some((piece_index, _, _)) = current_piece {
+        shared.lock().await.picker.release(piece_index);
+    }
+    if !peer_has.is_empty() {
+        shared.lock().await.picker.remove_peer_bitfield(&peer_has);
+    }
+    result
+}
+
+async fn peer_session_body(
+    stream: &mut TcpStream,
+    shared: &Arc<Mutex<TorrentShared>>,
+    upload_limiter: &Arc<RateLimiter>,
+    download_limiter: &Arc<RateLimiter>,
+    peer_has: &mut Vec<bool>,
+    current_piece: &mut Option<(u32, Vec<u8>, u32)>,
+    counted_as_seed: &mut bool,
+) -> Result<(), PeerError> {
+    stream.write_all(&PeerMessage::Interested.encode()).await?;
+
+    let mut peer_choking = true;
+    let mut in_flight: Vec<(u32, u32, u32)> = Vec::new();
+
+    loop {
+        let (piece_count, is_complete, abandoned) = {
+            let state = shared.lock().await;
+            let abandoned = current_piece.as_ref().is_some_and(|(index, _, _)| {
+                state.completed_pieces.get(*index as usize).copied().unwrap_or(false)
+            });
+            (state.piece_count(), state.is_complete(), abandoned)
+        };
+        if is_complete {
+            return Ok(());
+        }
+        if peer_has.is_empty() && piece_count > 0 {
+            *peer_has = vec![false; piece_count];
+        }
+
+        // Another peer finished this piece first (only possible in
+        // endgame mode, where we deliberately request the same piece from
+        // more than one peer); cancel our own outstanding requests for it.
+        if abandoned {
+            if let Some((piece_index, _, _)) = current_piece.take() {
+                for (index, begin, length) in in_flight.drain(..) {
+                    stream
+                        .write_all(&PeerMessage::Cancel { index, begin, length }.encode())
+                        .await?;
+                }
+                shared.lock().await.picker.release(piece_index);
+            }
+        }
+
+        let message = read_message(stream).await?;
+        match message {
+            PeerMessage::KeepAlive => {}
+            PeerMessage::Choke => peer_choking = true,
+            PeerMessage::Unchoke => peer_choking = false,
+            PeerMessage::Interested | PeerMessage::NotInterested => {}
+            PeerMessage::Have(index) => {
+                if let Some(slot) = peer_has.get_mut(index as usize) {
+                    if !*slot {
+                        *slot = true;
+                        shared.lock().await.picker.add_have(index);
+                    }
+                }
+            }
+            PeerMessage::Bitfield(bits) => {
+                for (i, has) in peer_has.iter_mut().enumerate() {
+                    let byte = bits.get(i / 8).copied().unwrap_or(0);
+                    *has = byte & (0x80 >> (i % 8)) != 0;
+                }
+                let mut state = shared.lock().await;
+                state.picker.add_peer_bitfield(peer_has);
+                if !*counted_as_seed && peer_has.iter().all(|has| *has) {
+                    state.seeds += 1;
+                    *counted_as_seed = true;
+                }
+            }
+            PeerMessage::Request { index, begin, length } => {
+                let have_piece = {
+                    let state = shared.lock().await;
+                    state.completed_pieces.get(index as usize).copied().unwrap_or(false)
+                };
+                if have_piece {
+                    serve_request(stream, shared, upload_limiter, index, begin, length).await?;
+                }
+            }
+            PeerMessage::Cancel { .. } => {
+                // Requests are served synchronously as they arrive, so
+                // there's nothing queued to cancel.
+            }
+            PeerMessage::Extended { .. } => {
+                // The metadata exchange runs before this loop starts; any
+                // further extended messages (e.g. peer exchange) are unused.
+            }
+            PeerMessage::Piece { index, begin, block } => {
+                in_flight.retain(|(i, b, _)| *i != index || *b != begin);
+                download_limiter.acquire(block.len() as u32).await;
+                if let Some((piece_index, buf, _)) = current_piece.as_mut() {
+                    if *piece_index == index {
+                        let offset = begin as usize;
+                        if buf.len() < offset + block.len() {
+                            buf.resize(offset + block.len(), 0);
+                        }
+                        buf[offset..offset + block.len()].copy_from_slice(&block);
+                    }
+                }
+            }
+        }
+
+        if !peer_choking {
+            if current_piece.is_none() {
+                *current_piece = pick_next_piece(shared, peer_has).await;
+            }
+
+            if current_piece.is_some() {
+                let piece_index = current_piece.as_ref().unwrap().0;
+                let piece_size = shared.lock().await.piece_size(piece_index);
+
+                // `next_begin` tracks the next byte offset to request, so
+                // it keeps advancing past blocks already acked even as
+                // `in_flight` shrinks when they come back; deriving the
+                // offset from `in_flight.len()` instead would re-request
+                // the same low offsets forever once the pipeline fills up.
+                while in_flight.len() < PIPELINE_DEPTH {
+                    let next_begin = current_piece.as_ref().unwrap().2;
+                    if next_begin >= piece_size {
+                        break;
+                    }
+                    let length = BLOCK_SIZE.min(piece_size - next_begin);
+                    stream
+                        .write_all(
+                            &PeerMessage::Request {
+                                index: piece_index,
+                                begin: next_begin,
+                                length,
+                            }
+                            .encode(),
+                        )
+                        .await?;
+                    in_flight.push((piece_index, next_begin, length));
+                    current_piece.as_mut().unwrap().2 += length;
+                }
+
+                let piece_size = piece_size as usize;
+                let piece_ready = current_piece
+                    .as_ref()
+                    .map(|(_, buf, _)| buf.len() >= piece_size)
+                    .unwrap_or(false);
+                if piece_ready {
+                    let (piece_index, buf, _) = current_piece.take().unwrap();
+                    finish_piece(shared, piece_index, buf).await?;
+                    in_flight.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Pick the rarest piece we still need that this peer claims to have,
+/// deferring to the shared picker so every connected peer's task agrees on
+/// which piece is rarest and which pieces are already spoken for.
+async fn pick_next_piece(
+    shared: &Arc<Mutex<TorrentShared>>,
+    peer_has: &[bool],
+) -> Option<(u32, Vec<u8>, u32)> {
+    let mut state = shared.lock().await;
+    let completed = state.completed_pieces.clone();
+    let index = state.picker.pick(&completed, peer_has)?;
+    Some((index, Vec::new(), 0))
+}
+
+/// Serve a peer's `Request` for a block of a piece we've already verified:
+/// read it off disk, throttle it against the upload limiter, and send it
+/// back as a `Piece` message.
+async fn serve_request(
+    stream: &mut TcpStream,
+    shared: &Arc<Mutex<TorrentShared>>,
+    upload_limiter: &Arc<RateLimiter>,
+    index: u32,
+    begin: u32,
+    length: u32,
+) -> Result<(), PeerError> {
+    let (files, piece_length, download_path, piece_size) = {
+        let state = shared.lock().await;
+        (
+            state.files.clone(),
+            state.piece_length,
+            state.base_download_path.join(&state.name),
+            state.piece_size(index),
+        )
+    };
+
+    // `length` is peer-controlled; clamp it to BLOCK_SIZE and to what's
+    // actually left in the piece so a malicious or buggy Request can't
+    // force an oversized read/allocation in `read_block`.
+    if begin >= piece_size {
+        return Ok(());
+    }
+    let length = length.min(BLOCK_SIZE).min(piece_size - begin);
+    if length == 0 {
+        return Ok(());
+    }
+
+    let Ok(block) = read_block(&files, piece_length, index, begin, length, &download_path) else {
+        return Ok(());
+    };
+
+    upload_limiter.acquire(length).await;
+    let sent = block.len() as u64;
+    stream
+        .write_all(&PeerMessage::Piece { index, begin, block }.encode())
+        .await?;
+    shared.lock().await.uploaded += sent;
+    Ok(())
+}
+
+async fn finish_piece(
+    shared: &Arc<Mutex<TorrentShared>>,
+    piece_index: u32,
+    data: Vec<u8>,
+) -> Result<(), PeerError> {
+    let mut hasher = Sha1::new();
+    hasher.update(&data);
+    let digest: [u8; 20] = hasher.finalize().into();
+
+    let mut state = shared.lock().await;
+    let expected = state.piece_hashes.get(piece_index as usize).copied();
+    if expected != Some(digest) {
+        // Corrupt or mismatched piece; drop it and let it be re-requested.
+        state.picker.release(piece_index);
+        return Ok(());
+    }
+
+    write_piece(
+        &state.files,
+        state.piece_length,
+        piece_index,
+        &data,
+        &state.base_download_path.join(&state.name),
+    )?;
+
+    if let Some(slot) = state.completed_pieces.get_mut(piece_index as usize) {
+        if !*slot {
+            *slot = true;
+            state.downloaded += data.len() as u64;
+        }
+    }
+    state.picker.release(piece_index);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_body(encoded: &[u8]) -> PeerMessage {
+        let len = u32::from_be_bytes(encoded[0..4].try_into().unwrap()) as usize;
+        assert_eq!(encoded.len(), 4 + len);
+        if len == 0 {
+            return PeerMessage::KeepAlive;
+        }
+        PeerMessage::decode(&encoded[4..]).expect("message should decode")
+    }
+
+    #[test]
+    fn round_trips_every_message_variant() {
+        let messages = vec![
+            PeerMessage::Choke,
+            PeerMessage::Unchoke,
+            PeerMessage::Interested,
+            PeerMessage::NotInterested,
+            PeerMessage::Have(7),
+            PeerMessage::Bitfield(vec![0b1010_0000, 0b0000_0001]),
+            PeerMessage::Request { index: 1, begin: 16384, length: 16384 },
+            PeerMessage::Piece { index: 1, begin: 0, block: vec![1, 2, 3, 4] },
+            PeerMessage::Cancel { index: 1, begin: 16384, length: 16384 },
+            PeerMessage::Extended { id: 1, payload: vec![b'd', b'e'] },
+        ];
+
+        for message in messages {
+            assert_eq!(decode_body(&message.encode()), message);
+        }
+    }
+
+    #[test]
+    fn keep_alive_round_trips_as_a_zero_length_message() {
+        assert_eq!(decode_body(&PeerMessage::KeepAlive.encode()), PeerMessage::KeepAlive);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_and_unknown_messages() {
+        assert_eq!(PeerMessage::decode(&[6, 0, 0]), None); // Request, too short
+        assert_eq!(PeerMessage::decode(&[255]), None); // unknown message id
+    }
+
+    fn file(path: &str, size: u64) -> FileInfo {
+        FileInfo { path: PathBuf::from(path), size }
+    }
+
+    #[test]
+    fn piece_file_ranges_stays_within_a_single_file() {
+        let files = vec![file("a.bin", 1000), file("b.bin", 1000)];
+        let ranges = piece_file_ranges(&files, 500, 1, 0, 500);
+
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].path, PathBuf::from("a.bin"));
+        assert_eq!(ranges[0].offset, 500);
+        assert_eq!(ranges[0].data_start, 0);
+        assert_eq!(ranges[0].data_len, 500);
+    }
+
+    #[test]
+    fn piece_file_ranges_splits_across_a_file_boundary() {
+        let files = vec![file("a.bin", 300), file("b.bin", 1000)];
+        // Piece 0 covers bytes [0, 500): 300 bytes from a.bin, 200 from b.bin.
+        let ranges = piece_file_ranges(&files, 500, 0, 0, 500);
+
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].path, PathBuf::from("a.bin"));
+        assert_eq!(ranges[0].offset, 0);
+        assert_eq!(ranges[0].data_start, 0);
+        assert_eq!(ranges[0].data_len, 300);
+
+        assert_eq!(ranges[1].path, PathBuf::from("b.bin"));
+        assert_eq!(ranges[1].offset, 0);
+        assert_eq!(ranges[1].data_start, 300);
+        assert_eq!(ranges[1].data_len, 200);
+    }
+
+    #[test]
+    fn piece_file_ranges_respects_a_nonzero_piece_offset() {
+        let files = vec![file("a.bin", 1000)];
+        // A block starting partway into the piece.
+        let ranges = piece_file_ranges(&files, 1000, 0, 100, 50);
+
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].offset, 100);
+        assert_eq!(ranges[0].data_start, 0);
+        assert_eq!(ranges[0].data_len, 50);
+    }
+}
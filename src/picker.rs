@@ -0,0 +1,105 @@
+//! Piece selection: rarest-first ordering across all connected peers, with
+//! an endgame mode for the final stretch of a download.
+
+use std::collections::HashSet;
+
+/// Once this many or fewer pieces remain, every peer that claims to have a
+/// missing piece is asked for it, duplicates and all — waiting on one slow
+/// peer to finish the last few pieces alone costs more than the wasted
+/// bandwidth of a few redundant requests.
+const ENDGAME_THRESHOLD: usize = 20;
+
+/// Tracks how many connected peers have each piece, and which pieces are
+/// already being fetched, so the rarest still-missing piece is always
+/// requested next.
+pub struct PiecePicker {
+    /// Number of connected peers known to have each piece.
+    availability: Vec<u32>,
+    /// Pieces currently assigned to some peer's download loop. Outside
+    /// endgame mode this prevents two peers racing for the same piece.
+    assigned: HashSet<u32>,
+}
+
+impl PiecePicker {
+    pub fn new(piece_count: usize) -> Self {
+        Self {
+            availability: vec![0; piece_count],
+            assigned: HashSet::new(),
+        }
+    }
+
+    /// The current known availability of each piece, for display.
+    pub fn availability(&self) -> &[u32] {
+        &self.availability
+    }
+
+    /// Record that a peer's bitfield reports having these pieces.
+    pub fn add_peer_bitfield(&mut self, peer_has: &[bool]) {
+        for (index, has) in peer_has.iter().enumerate() {
+            if *has {
+                if let Some(count) = self.availability.get_mut(index) {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    /// Undo `add_peer_bitfield` when a peer disconnects.
+    pub fn remove_peer_bitfield(&mut self, peer_has: &[bool]) {
+        for (index, has) in peer_has.iter().enumerate() {
+            if *has {
+                if let Some(count) = self.availability.get_mut(index) {
+                    *count = count.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    /// Record a single `have` announcement from a peer.
+    pub fn add_have(&mut self, index: u32) {
+        if let Some(count) = self.availability.get_mut(index as usize) {
+            *count += 1;
+        }
+    }
+
+    /// Whether endgame mode should be active given how many pieces are
+    /// still missing.
+    pub fn is_endgame(&self, missing: usize) -> bool {
+        missing > 0 && missing <= ENDGAME_THRESHOLD
+    }
+
+    /// Pick the rarest piece that `peer_has` claims to have and isn't
+    /// already done. Outside endgame mode, pieces already assigned to
+    /// another peer are skipped; during endgame every missing piece is a
+    /// candidate, since requesting the same piece from multiple peers is
+    /// the point.
+    pub fn pick(&mut self, completed: &[bool], peer_has: &[bool]) -> Option<u32> {
+        let missing = completed.iter().filter(|done| !**done).count();
+        let endgame = self.is_endgame(missing);
+
+        let mut best: Option<(u32, u32)> = None;
+        for (index, done) in completed.iter().enumerate() {
+            if *done || !peer_has.get(index).copied().unwrap_or(false) {
+                continue;
+            }
+            let index = index as u32;
+            if !endgame && self.assigned.contains(&index) {
+                continue;
+            }
+            let rarity = self.availability.get(index as usize).copied().unwrap_or(0);
+            if best.map(|(best_rarity, _)| rarity < best_rarity).unwrap_or(true) {
+                best = Some((rarity, index));
+            }
+        }
+
+        let (_, index) = best?;
+        self.assigned.insert(index);
+        Some(index)
+    }
+
+    /// Release a piece that was abandoned before it finished, so another
+    /// peer (or the same one) can pick it up again.
+    pub fn release(&mut self, index: u32) {
+        self.assigned.remove(&index);
+    }
+}
@@ -0,0 +1,74 @@
+//! A token-bucket rate limiter shared across every peer connection, so a
+//! configured upload/download cap applies globally rather than per-peer.
+
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Caps throughput at a fixed number of bytes per second; `None` (from
+/// `AppConfig`'s `None` = unlimited) disables throttling entirely.
+pub struct RateLimiter {
+    rate_bytes_per_sec: Option<u64>,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    /// Bytes of budget currently available to spend.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// `limit_kb_s` is the configured KB/s cap; `None` means unlimited.
+    pub fn new(limit_kb_s: Option<u64>) -> Self {
+        let rate_bytes_per_sec = limit_kb_s.map(|kb| kb * 1024);
+        Self {
+            rate_bytes_per_sec,
+            state: Mutex::new(BucketState {
+                tokens: rate_bytes_per_sec.unwrap_or(0) as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Block until `bytes` worth of budget is available, then spend it.
+    /// A no-op when unlimited.
+    pub async fn acquire(&self, bytes: u32) {
+        let Some(rate) = self.rate_bytes_per_sec else {
+            return;
+        };
+        if rate == 0 {
+            return;
+        }
+        let bytes = bytes as f64;
+        // The bucket's burst capacity must cover whatever is being
+        // requested right now, or a limit configured below the request
+        // size could never accumulate enough tokens to satisfy it and
+        // `acquire` would spin forever. `bytes` is peer-controlled (wire
+        // message lengths aren't clamped to BLOCK_SIZE), so size the
+        // capacity off the request itself rather than a fixed constant.
+        let capacity = (rate as f64).max(bytes);
+
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * rate as f64).min(capacity);
+                state.last_refill = now;
+
+                if state.tokens >= bytes {
+                    state.tokens -= bytes;
+                    None
+                } else {
+                    let deficit = bytes - state.tokens;
+                    Some(Duration::from_secs_f64(deficit / rate as f64))
+                }
+            };
+
+            match wait {
+                None => return,
+                Some(duration) => tokio::time::sleep(duration).await,
+            }
+        }
+    }
+}
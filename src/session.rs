@@ -0,0 +1,103 @@
+//! Fast-resume: persisting each torrent's download state to disk so it
+//! survives an application restart instead of starting over.
+
+use crate::peer::piece_file_ranges;
+use crate::torrent_engine::{FileInfo, TorrentInfo};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// How a torrent was originally added, so it can be re-added on resume.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TorrentSource {
+    Magnet(String),
+    TorrentFile(PathBuf),
+}
+
+/// Everything needed to resume a torrent without re-fetching metadata or
+/// re-downloading already-verified pieces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeEntry {
+    pub source: TorrentSource,
+    pub info: TorrentInfo,
+    pub completed_pieces: Vec<bool>,
+    pub downloaded: u64,
+    pub uploaded: u64,
+    pub paused: bool,
+}
+
+fn session_path() -> Result<PathBuf, String> {
+    let dir = dirs::config_dir()
+        .ok_or("Could not find config directory")?
+        .join("cosmic-torrent");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("session.bin"))
+}
+
+/// Persist the current resume state for every torrent, overwriting any
+/// previous session file.
+pub fn save(entries: &[ResumeEntry]) -> Result<(), String> {
+    let path = session_path()?;
+    let bytes = bincode::serialize(entries).map_err(|e| e.to_string())?;
+    std::fs::write(path, bytes).map_err(|e| e.to_string())
+}
+
+/// Load the persisted resume entries, if any session file exists yet.
+pub fn load() -> Result<Vec<ResumeEntry>, String> {
+    let path = session_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    bincode::deserialize(&bytes).map_err(|e| e.to_string())
+}
+
+/// Re-hash every piece already on disk against the torrent's piece hashes,
+/// rebuilding a trustworthy completed-pieces bitfield so only the pieces
+/// that are genuinely missing or corrupt get re-downloaded.
+pub fn verify_pieces_on_disk(
+    files: &[FileInfo],
+    piece_length: u64,
+    piece_hashes: &[[u8; 20]],
+    torrent_path: &Path,
+) -> Vec<bool> {
+    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    let mut verified = vec![false; piece_hashes.len()];
+
+    for (index, expected) in piece_hashes.iter().enumerate() {
+        let start = index as u64 * piece_length;
+        let size = total_size.saturating_sub(start).min(piece_length) as usize;
+        if size == 0 {
+            continue;
+        }
+
+        let mut buffer = vec![0u8; size];
+        if read_piece(files, piece_length, index as u32, &mut buffer, torrent_path).is_ok() {
+            let mut hasher = Sha1::new();
+            hasher.update(&buffer);
+            let digest: [u8; 20] = hasher.finalize().into();
+            verified[index] = &digest == expected;
+        }
+    }
+    verified
+}
+
+fn read_piece(
+    files: &[FileInfo],
+    piece_length: u64,
+    piece_index: u32,
+    buffer: &mut [u8],
+    torrent_path: &Path,
+) -> std::io::Result<()> {
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom};
+
+    for range in piece_file_ranges(files, piece_length, piece_index, 0, buffer.len()) {
+        let full_path = torrent_path.join(&range.path);
+        let mut handle = File::open(&full_path)?;
+        handle.seek(SeekFrom::Start(range.offset))?;
+        handle.read_exact(&mut buffer[range.data_start..range.data_start + range.data_len])?;
+    }
+    Ok(())
+}
@@ -1,12 +1,23 @@
+use crate::config::AppConfig;
+use crate::dht::DhtNode;
+use crate::peer::{self, TorrentShared};
+use crate::ratelimit::RateLimiter;
+use crate::session::{ResumeEntry, TorrentSource};
+use crate::tracker::{self, AnnounceEvent, AnnounceRequest};
 use lava_torrent::torrent::v1::Torrent;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
 use url::Url;
 
 /// Metadata and file information for a torrent.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TorrentInfo {
     /// The display name of the torrent.
     pub name: String,
@@ -18,10 +29,14 @@ pub struct TorrentInfo {
     pub announce_urls: Vec<String>,
     /// List of files contained in the torrent.
     pub files: Vec<FileInfo>,
+    /// Length in bytes of a full piece.
+    pub piece_length: u64,
+    /// SHA-1 hash of each piece, in order.
+    pub piece_hashes: Vec<[u8; 20]>,
 }
 
 /// Information about a single file in a torrent.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
     /// The file path relative to the torrent root.
     pub path: PathBuf,
@@ -46,6 +61,8 @@ pub struct TorrentStats {
     pub peers: u32,
     /// Number of connected seeds.
     pub seeds: u32,
+    /// Number of connected peers known to have each piece, in piece order.
+    pub piece_availability: Vec<u32>,
 }
 
 /// Events emitted by the torrent engine to notify about state changes.
@@ -63,6 +80,11 @@ pub enum TorrentEvent {
     Paused(String),
     /// Torrent was resumed (info_hash).
     Resumed(String),
+    /// Seeding stopped automatically because the configured seed ratio or
+    /// seed time limit was reached (info_hash).
+    SeedingLimitReached(String),
+    /// Torrent was removed from the engine (info_hash).
+    Removed(String),
 }
 
 /// Main engine for managing torrents and their state.
@@ -73,6 +95,32 @@ pub struct TorrentEngine {
     torrents: HashMap<String, TorrentHandle>,
     /// Default download path for torrent data.
     download_path: PathBuf,
+    /// Our 20-byte peer id, used in handshakes with every peer.
+    peer_id: [u8; 20],
+    /// The port we advertise to trackers as our listening port.
+    listen_port: u16,
+    /// The DHT node used to find peers without a tracker, if enabled.
+    dht: Option<Arc<DhtNode>>,
+    /// Global cap on outgoing (upload) throughput, shared by every peer
+    /// connection across every torrent.
+    upload_limiter: Arc<RateLimiter>,
+    /// Global cap on incoming (download) throughput, shared by every peer
+    /// connection across every torrent.
+    download_limiter: Arc<RateLimiter>,
+    /// Stop seeding once `uploaded / downloaded` reaches this ratio.
+    seed_ratio_limit: Option<f32>,
+    /// Stop seeding once this many seconds have passed since completion.
+    seed_time_limit: Option<u64>,
+}
+
+/// A read-only snapshot of one managed torrent, for external consumers
+/// (like the web API) that shouldn't reach into the engine's internals.
+#[derive(Debug, Clone)]
+pub struct TorrentSummary {
+    pub info_hash: String,
+    pub info: TorrentInfo,
+    pub stats: TorrentStats,
+    pub paused: bool,
 }
 
 /// Internal handle for a managed torrent.
@@ -83,27 +131,247 @@ struct TorrentHandle {
     stats: TorrentStats,
     /// Whether the torrent is paused.
     paused: bool,
+    /// State shared with every spawned peer connection task for this torrent.
+    shared: Arc<Mutex<TorrentShared>>,
+    /// How this torrent was originally added, so it can be resumed later.
+    source: TorrentSource,
+    /// When this torrent first reached 100% progress, for measuring how
+    /// long it's been seeding against `seed_time_limit`. Reset to `None`
+    /// if the torrent somehow becomes incomplete again.
+    seeding_since: Option<Instant>,
 }
 
 impl TorrentEngine {
     /// Create a new torrent engine and event receiver.
-    pub fn new() -> (Self, mpsc::UnboundedReceiver<TorrentEvent>) {
+    pub fn new(config: &AppConfig) -> (Self, mpsc::UnboundedReceiver<TorrentEvent>) {
         let (tx, rx) = mpsc::unbounded_channel();
 
-        // Set default download path
-        let download_path = dirs::download_dir()
-            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/tmp")));
+        let dht = if config.enable_dht {
+            let bootstrap_nodes = if config.dht_bootstrap_nodes.is_empty() {
+                crate::dht::DEFAULT_BOOTSTRAP_NODES
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            } else {
+                config.dht_bootstrap_nodes.clone()
+            };
+            let node = Arc::new(DhtNode::new(bootstrap_nodes));
+            tokio::spawn({
+                let node = node.clone();
+                async move { node.bootstrap().await }
+            });
+            Some(node)
+        } else {
+            None
+        };
 
         (
             Self {
                 event_sender: tx,
                 torrents: HashMap::new(),
-                download_path,
+                download_path: config.download_directory.clone(),
+                peer_id: generate_peer_id(),
+                listen_port: config.listen_port,
+                dht,
+                upload_limiter: Arc::new(RateLimiter::new(config.upload_limit)),
+                download_limiter: Arc::new(RateLimiter::new(config.download_limit)),
+                seed_ratio_limit: config.seed_ratio_limit,
+                seed_time_limit: config.seed_time_limit,
             },
             rx,
         )
     }
 
+    /// Connect to a peer for the given torrent and start downloading from it.
+    ///
+    /// Spawns a background task that performs the handshake and runs the
+    /// block download loop for as long as the connection stays alive.
+    pub fn connect_peer(&self, info_hash: &str, addr: SocketAddr) {
+        let Some(handle) = self.torrents.get(info_hash) else {
+            return;
+        };
+        let Ok(hash_bytes) = hex::decode(info_hash) else {
+            return;
+        };
+        if hash_bytes.len() != 20 {
+            return;
+        }
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&hash_bytes);
+
+        let shared = handle.shared.clone();
+        let peer_id = self.peer_id;
+        let event_sender = self.event_sender.clone();
+        let info_hash = info_hash.to_string();
+        let upload_limiter = self.upload_limiter.clone();
+        let download_limiter = self.download_limiter.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) =
+                peer::run_peer_session(addr, hash, peer_id, shared, upload_limiter, download_limiter)
+                    .await
+            {
+                let _ = event_sender.send(TorrentEvent::Error(info_hash, e.to_string()));
+            }
+        });
+    }
+
+    /// Spawn a background task that periodically announces to every tracker
+    /// tier for a torrent and connects to the peers it returns.
+    ///
+    /// `announce_urls` is a flat list (the `announce` URL, then every
+    /// `announce-list` tier in order); each round tries the URLs in that
+    /// order and stops at the first one that answers. Tier boundaries are
+    /// not tracked, and URLs are not shuffled or reordered after a success.
+    fn spawn_tracker_loop(&self, info_hash: &str, announce_urls: &[String]) {
+        if announce_urls.is_empty() {
+            return;
+        }
+
+        let Ok(hash_bytes) = hex::decode(info_hash) else {
+            return;
+        };
+        if hash_bytes.len() != 20 {
+            return;
+        }
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&hash_bytes);
+
+        let Some(handle) = self.torrents.get(info_hash) else {
+            return;
+        };
+        let shared = handle.shared.clone();
+        let peer_id = self.peer_id;
+        let listen_port = self.listen_port;
+        let event_sender = self.event_sender.clone();
+        let info_hash = info_hash.to_string();
+        let announce_urls = announce_urls.to_vec();
+        let upload_limiter = self.upload_limiter.clone();
+        let download_limiter = self.download_limiter.clone();
+
+        tokio::spawn(async move {
+            let mut first_announce = true;
+            loop {
+                let (downloaded, uploaded, left) = {
+                    let state = shared.lock().await;
+                    (
+                        state.downloaded,
+                        state.uploaded,
+                        state.total_size.saturating_sub(state.downloaded),
+                    )
+                };
+                let event = if first_announce {
+                    AnnounceEvent::Started
+                } else {
+                    AnnounceEvent::None
+                };
+
+                let mut interval = 1800u64;
+                let mut last_error = None;
+                for url in &announce_urls {
+                    let request = AnnounceRequest {
+                        info_hash: hash,
+                        peer_id,
+                        port: listen_port,
+                        uploaded,
+                        downloaded,
+                        left,
+                        event,
+                        url,
+                    };
+                    match tracker::announce(&request).await {
+                        Ok(response) => {
+                            interval = response.interval.max(60);
+                            for addr in response.peers {
+                                tokio::spawn({
+                                    let shared = shared.clone();
+                                    let upload_limiter = upload_limiter.clone();
+                                    let download_limiter = download_limiter.clone();
+                                    async move {
+                                        let _ = peer::run_peer_session(
+                                            addr,
+                                            hash,
+                                            peer_id,
+                                            shared,
+                                            upload_limiter,
+                                            download_limiter,
+                                        )
+                                        .await;
+                                    }
+                                });
+                            }
+                            last_error = None;
+                            break;
+                        }
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+
+                if let Some(e) = last_error {
+                    let _ = event_sender.send(TorrentEvent::Error(info_hash.clone(), e));
+                }
+
+                first_announce = false;
+                if shared.lock().await.is_complete() {
+                    return;
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
+            }
+        });
+    }
+
+    /// Spawn a background task that periodically searches the DHT for peers
+    /// of a torrent and connects to whatever it finds.
+    fn spawn_dht_loop(&self, info_hash: &str) {
+        let Some(dht) = self.dht.clone() else { return };
+        let Ok(hash_bytes) = hex::decode(info_hash) else {
+            return;
+        };
+        if hash_bytes.len() != 20 {
+            return;
+        }
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&hash_bytes);
+
+        let Some(handle) = self.torrents.get(info_hash) else {
+            return;
+        };
+        let shared = handle.shared.clone();
+        let peer_id = self.peer_id;
+        let listen_port = self.listen_port;
+        let upload_limiter = self.upload_limiter.clone();
+        let download_limiter = self.download_limiter.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let peers = dht.get_peers(&hash, listen_port).await;
+                for addr in peers {
+                    tokio::spawn({
+                        let shared = shared.clone();
+                        let upload_limiter = upload_limiter.clone();
+                        let download_limiter = download_limiter.clone();
+                        async move {
+                            let _ = peer::run_peer_session(
+                                addr,
+                                hash,
+                                peer_id,
+                                shared,
+                                upload_limiter,
+                                download_limiter,
+                            )
+                            .await;
+                        }
+                    });
+                }
+
+                if shared.lock().await.is_complete() {
+                    return;
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(15 * 60)).await;
+            }
+        });
+    }
+
     /// Add a torrent from a magnet URL.
     ///
     /// Returns the info hash on success.
@@ -117,36 +385,30 @@ impl TorrentEngine {
 
         // Extract info hash from magnet URL
         let info_hash = self.extract_info_hash(&url)?;
-
-        // Create mock torrent info for demo
+        let announce_urls = url
+            .query_pairs()
+            .filter(|(key, _)| key == "tr")
+            .map(|(_, value)| value.into_owned())
+            .collect::<Vec<_>>();
+
+        // Real file metadata (name, size, piece hashes) isn't known yet for a
+        // magnet link until it's fetched from peers via the ut_metadata
+        // extension; placeholder values are used until that lands and
+        // `TorrentEvent::Added` is re-emitted with the real info.
         let torrent_info: TorrentInfo = TorrentInfo {
             name: format!("Torrent {}", info_hash),
-            size: 1024 * 1024 * 100, // 100MB
+            size: 0,
             info_hash: info_hash.clone(),
-            announce_urls: vec!["http://tracker.example.com:8080/announce".to_string()],
-            files: vec![FileInfo {
-                path: PathBuf::from("example_file.txt"),
-                size: 1024 * 1024 * 100,
-            }],
-        };
-
-        let stats: TorrentStats = TorrentStats {
-            downloaded: 0,
-            uploaded: 0,
-            download_rate: 0,
-            upload_rate: 0,
-            progress: 0.0,
-            peers: 0,
-            seeds: 0,
-        };
-
-        let handle = TorrentHandle {
-            info: torrent_info.clone(),
-            stats,
-            paused: false,
+            announce_urls,
+            files: vec![],
+            piece_length: 0,
+            piece_hashes: vec![],
         };
 
+        let handle = self.make_handle(torrent_info.clone(), TorrentSource::Magnet(magnet_url.to_string()));
         self.torrents.insert(info_hash.clone(), handle);
+        self.spawn_tracker_loop(&info_hash, &torrent_info.announce_urls);
+        self.spawn_dht_loop(&info_hash);
 
         // Send event
         let _ = self
@@ -189,6 +451,16 @@ impl TorrentEngine {
 
         let total_size = files.iter().map(|f| f.size).sum();
 
+        let piece_hashes = torrent
+            .pieces
+            .iter()
+            .map(|piece| {
+                let mut hash = [0u8; 20];
+                hash.copy_from_slice(piece);
+                hash
+            })
+            .collect();
+
         let torrent_info = TorrentInfo {
             name: torrent.name.clone(),
             size: total_size,
@@ -208,25 +480,17 @@ impl TorrentEngine {
                 urls
             },
             files,
+            piece_length: torrent.piece_length as u64,
+            piece_hashes,
         };
 
-        let stats = TorrentStats {
-            downloaded: 0,
-            uploaded: 0,
-            download_rate: 0,
-            upload_rate: 0,
-            progress: 0.0,
-            peers: 0,
-            seeds: 0,
-        };
-
-        let handle = TorrentHandle {
-            info: torrent_info.clone(),
-            stats,
-            paused: false,
-        };
-
+        let handle = self.make_handle(
+            torrent_info.clone(),
+            TorrentSource::TorrentFile(PathBuf::from(file_path)),
+        );
         self.torrents.insert(info_hash.clone(), handle);
+        self.spawn_tracker_loop(&info_hash, &torrent_info.announce_urls);
+        self.spawn_dht_loop(&info_hash);
 
         // Send event
         let _ = self
@@ -262,9 +526,25 @@ impl TorrentEngine {
         }
     }
 
+    /// Snapshot every managed torrent's current info and stats.
+    pub fn list_torrents(&self) -> Vec<TorrentSummary> {
+        self.torrents
+            .iter()
+            .map(|(info_hash, handle)| TorrentSummary {
+                info_hash: info_hash.clone(),
+                info: handle.info.clone(),
+                stats: handle.stats.clone(),
+                paused: handle.paused,
+            })
+            .collect()
+    }
+
     /// Remove a torrent by its info hash.
     pub fn remove_torrent(&mut self, info_hash: &str) -> Result<(), String> {
         if self.torrents.remove(info_hash).is_some() {
+            let _ = self
+                .event_sender
+                .send(TorrentEvent::Removed(info_hash.to_string()));
             Ok(())
         } else {
             Err("Torrent not found".to_string())
@@ -272,39 +552,207 @@ impl TorrentEngine {
     }
 
     /// Start the periodic update loop for torrent statistics and progress.
+    ///
+    /// This does not download anything itself; it polls the state that the
+    /// per-peer tasks (spawned by the tracker and DHT loops, or by
+    /// [`TorrentEngine::connect_peer`]) are concurrently writing into and
+    /// republishes it as `TorrentStats`.
     pub async fn start_update_loop(&mut self) {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
 
         loop {
             interval.tick().await;
+            self.tick().await;
+        }
+    }
 
-            // Update stats for all active torrents
-            for (info_hash, handle) in &mut self.torrents {
-                if !handle.paused && handle.stats.progress < 1.0 {
-                    // Simulate progress
-                    handle.stats.progress = (handle.stats.progress + 0.01).min(1.0);
-                    // Simulate some activity with simple incrementing values
-                    handle.stats.download_rate =
-                        (handle.stats.download_rate + 1024) % (1024 * 1024);
-                    handle.stats.upload_rate = (handle.stats.upload_rate + 512) % (1024 * 512);
-                    handle.stats.peers = (handle.stats.peers % 50) + 1;
-                    handle.stats.seeds = (handle.stats.seeds % 20) + 1;
-                    handle.stats.downloaded =
-                        (handle.info.size as f32 * handle.stats.progress) as u64;
-
-                    let _ = self.event_sender.send(TorrentEvent::Progress(
-                        info_hash.clone(),
-                        handle.stats.clone(),
-                    ));
-
-                    if handle.stats.progress >= 1.0 {
-                        let _ = self
-                            .event_sender
-                            .send(TorrentEvent::Completed(info_hash.clone()));
-                    }
+    /// Run a single update tick: refresh every torrent's stats from its
+    /// shared peer state, emit the resulting events, and persist the
+    /// session. Split out of [`TorrentEngine::start_update_loop`] so a
+    /// caller sharing the engine behind a lock (e.g. `run_backend` in
+    /// `main.rs`, which shares it with the web API) can run the loop itself
+    /// without holding the lock for the engine's entire lifetime.
+    pub async fn tick(&mut self) {
+        for (info_hash, handle) in &mut self.torrents {
+            if handle.paused {
+                continue;
+            }
+
+            let state = handle.shared.lock().await;
+            let downloaded = state.downloaded;
+            let was_complete = handle.stats.progress >= 1.0;
+            let progress = state.progress();
+
+            // A magnet link's real metadata arrives asynchronously once
+            // a peer's ut_metadata exchange completes; notice it here
+            // and republish the torrent with its real name/size/files.
+            let newly_known_metadata =
+                handle.info.piece_hashes.is_empty() && !state.piece_hashes.is_empty();
+            if newly_known_metadata {
+                handle.info.name = state.name.clone();
+                handle.info.size = state.total_size;
+                handle.info.piece_length = state.piece_length;
+                handle.info.piece_hashes = state.piece_hashes.clone();
+                handle.info.files = state.files.clone();
+            }
+
+            handle.stats.download_rate = downloaded.saturating_sub(handle.stats.downloaded);
+            handle.stats.downloaded = downloaded;
+            handle.stats.upload_rate = state.uploaded.saturating_sub(handle.stats.uploaded);
+            handle.stats.uploaded = state.uploaded;
+            handle.stats.progress = progress;
+            handle.stats.peers = state.peers;
+            handle.stats.seeds = state.seeds;
+            handle.stats.piece_availability = state.picker.availability().to_vec();
+            drop(state);
+
+            if newly_known_metadata {
+                let _ = self
+                    .event_sender
+                    .send(TorrentEvent::Added(info_hash.clone(), handle.info.clone()));
+            }
+
+            let _ = self
+                .event_sender
+                .send(TorrentEvent::Progress(info_hash.clone(), handle.stats.clone()));
+
+            if !was_complete && handle.stats.progress >= 1.0 {
+                let _ = self
+                    .event_sender
+                    .send(TorrentEvent::Completed(info_hash.clone()));
+            }
+
+            if handle.stats.progress >= 1.0 {
+                let seeding_since = *handle.seeding_since.get_or_insert_with(Instant::now);
+                let ratio_exceeded = self.seed_ratio_limit.is_some_and(|limit| {
+                    handle.stats.downloaded > 0
+                        && handle.stats.uploaded as f64 / handle.stats.downloaded as f64
+                            >= limit as f64
+                });
+                let time_exceeded = self
+                    .seed_time_limit
+                    .is_some_and(|limit| seeding_since.elapsed().as_secs() >= limit);
+
+                if (ratio_exceeded || time_exceeded) && !handle.paused {
+                    handle.paused = true;
+                    let _ = self
+                        .event_sender
+                        .send(TorrentEvent::SeedingLimitReached(info_hash.clone()));
                 }
+            } else {
+                handle.seeding_since = None;
+            }
+        }
+
+        let _ = self.save_session().await;
+    }
+
+    /// Build a fresh `TorrentHandle` (stats and shared peer state) for a
+    /// newly added torrent.
+    fn make_handle(&self, info: TorrentInfo, source: TorrentSource) -> TorrentHandle {
+        let piece_count = info.piece_hashes.len();
+        let shared = TorrentShared {
+            info_hash: info.info_hash.clone(),
+            name: info.name.clone(),
+            piece_hashes: info.piece_hashes.clone(),
+            piece_length: info.piece_length,
+            total_size: info.size,
+            files: info.files.clone(),
+            base_download_path: self.download_path.clone(),
+            completed_pieces: vec![false; piece_count],
+            downloaded: 0,
+            uploaded: 0,
+            peers: 0,
+            seeds: 0,
+            picker: crate::picker::PiecePicker::new(piece_count),
+        };
+
+        TorrentHandle {
+            info,
+            stats: TorrentStats {
+                downloaded: 0,
+                uploaded: 0,
+                download_rate: 0,
+                upload_rate: 0,
+                progress: 0.0,
+                peers: 0,
+                seeds: 0,
+                piece_availability: vec![0; piece_count],
+            },
+            paused: false,
+            shared: Arc::new(Mutex::new(shared)),
+            source,
+            seeding_since: None,
+        }
+    }
+
+    /// Persist resume state (source, metadata, verified pieces, totals) for
+    /// every managed torrent, overwriting any previous session file.
+    pub async fn save_session(&self) -> Result<(), String> {
+        let mut entries = Vec::with_capacity(self.torrents.len());
+        for handle in self.torrents.values() {
+            let state = handle.shared.lock().await;
+            entries.push(ResumeEntry {
+                source: handle.source.clone(),
+                info: handle.info.clone(),
+                completed_pieces: state.completed_pieces.clone(),
+                downloaded: state.downloaded,
+                uploaded: state.uploaded,
+                paused: handle.paused,
+            });
+        }
+        crate::session::save(&entries)
+    }
+
+    /// Reload all persisted torrents, re-verifying their on-disk pieces
+    /// against the piece hashes before resuming whatever is still missing.
+    pub async fn load_session(&mut self) -> Result<(), String> {
+        let entries = crate::session::load()?;
+        for entry in entries {
+            let torrent_path = self.download_path.join(&entry.info.name);
+            let completed_pieces = crate::session::verify_pieces_on_disk(
+                &entry.info.files,
+                entry.info.piece_length,
+                &entry.info.piece_hashes,
+                &torrent_path,
+            );
+            let piece_size = entry.info.piece_length;
+            let downloaded: u64 = completed_pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, done)| **done)
+                .map(|(index, _)| {
+                    let start = index as u64 * piece_size;
+                    entry.info.size.saturating_sub(start).min(piece_size)
+                })
+                .sum();
+
+            let announce_urls = entry.info.announce_urls.clone();
+            let info_hash = entry.info.info_hash.clone();
+            let mut handle = self.make_handle(entry.info.clone(), entry.source);
+            handle.paused = entry.paused;
+            handle.stats.downloaded = downloaded;
+            handle.stats.uploaded = entry.uploaded;
+            handle.stats.progress = if entry.info.size == 0 {
+                0.0
+            } else {
+                downloaded as f32 / entry.info.size as f32
+            };
+
+            {
+                let mut state = handle.shared.lock().await;
+                state.completed_pieces = completed_pieces;
+                state.downloaded = downloaded;
+                state.uploaded = entry.uploaded;
+            }
+
+            self.torrents.insert(info_hash.clone(), handle);
+            if !entry.paused {
+                self.spawn_tracker_loop(&info_hash, &announce_urls);
+                self.spawn_dht_loop(&info_hash);
             }
         }
+        Ok(())
     }
 
     /// Extract the info hash from a magnet URL.
@@ -318,3 +766,12 @@ impl TorrentEngine {
         Err("No info hash found in magnet URL".to_string())
     }
 }
+
+/// Generate a random 20-byte peer id with the conventional Azureus-style
+/// `-XX0000-` prefix.
+fn generate_peer_id() -> [u8; 20] {
+    let mut id = [0u8; 20];
+    id[..8].copy_from_slice(b"-CT0001-");
+    rand::thread_rng().fill(&mut id[8..]);
+    id
+}
@@ -0,0 +1,288 @@
+//! Tracker announce support: HTTP(S) trackers per BEP 3 and UDP trackers
+//! per BEP 15.
+
+use lava_torrent::bencode::BencodeElem;
+use rand::Rng;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// The magic protocol id that must prefix a UDP tracker connect request.
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+const UDP_ACTION_CONNECT: u32 = 0;
+const UDP_ACTION_ANNOUNCE: u32 = 1;
+const UDP_ACTION_ERROR: u32 = 3;
+const UDP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The lifecycle event reported alongside a tracker announce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnounceEvent {
+    Started,
+    Stopped,
+    Completed,
+    None,
+}
+
+impl AnnounceEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnnounceEvent::Started => "started",
+            AnnounceEvent::Stopped => "stopped",
+            AnnounceEvent::Completed => "completed",
+            AnnounceEvent::None => "",
+        }
+    }
+
+    fn as_udp_code(&self) -> u32 {
+        match self {
+            AnnounceEvent::None => 0,
+            AnnounceEvent::Completed => 1,
+            AnnounceEvent::Started => 2,
+            AnnounceEvent::Stopped => 3,
+        }
+    }
+}
+
+/// Parameters common to both HTTP and UDP announces.
+pub struct AnnounceRequest<'a> {
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+    pub port: u16,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+    pub event: AnnounceEvent,
+    pub url: &'a str,
+}
+
+/// The interval and peer list returned by a tracker.
+#[derive(Debug, Clone)]
+pub struct TrackerResponse {
+    /// Seconds to wait before the next announce.
+    pub interval: u64,
+    /// Peers the tracker knows about for this torrent.
+    pub peers: Vec<SocketAddr>,
+}
+
+/// Announce to a single tracker URL, dispatching on its scheme.
+pub async fn announce(request: &AnnounceRequest<'_>) -> Result<TrackerResponse, String> {
+    if request.url.starts_with("http://") || request.url.starts_with("https://") {
+        announce_http(request).await
+    } else if request.url.starts_with("udp://") {
+        announce_udp(request).await
+    } else {
+        Err(format!("unsupported tracker scheme: {}", request.url))
+    }
+}
+
+/// Announce to an HTTP(S) tracker and parse its bencoded, compact response.
+async fn announce_http(request: &AnnounceRequest<'_>) -> Result<TrackerResponse, String> {
+    let mut url = url::Url::parse(request.url).map_err(|e| e.to_string())?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair(
+            "info_hash",
+            &String::from_utf8_lossy(&request.info_hash).into_owned(),
+        );
+        query.append_pair("peer_id", &String::from_utf8_lossy(&request.peer_id).into_owned());
+        query.append_pair("port", &request.port.to_string());
+        query.append_pair("uploaded", &request.uploaded.to_string());
+        query.append_pair("downloaded", &request.downloaded.to_string());
+        query.append_pair("left", &request.left.to_string());
+        query.append_pair("compact", "1");
+        if request.event != AnnounceEvent::None {
+            query.append_pair("event", request.event.as_str());
+        }
+    }
+    // `info_hash`/`peer_id` are raw bytes, not valid UTF-8 in general; encode
+    // them ourselves with percent-encoding so every byte round-trips.
+    let url = raw_encode_binary_params(&url, &request.info_hash, &request.peer_id);
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    parse_http_response(&bytes)
+}
+
+/// Rebuild the announce URL with `info_hash`/`peer_id` percent-encoded from
+/// raw bytes, since `url::form_urlencoded` assumes UTF-8 input.
+fn raw_encode_binary_params(base: &url::Url, info_hash: &[u8; 20], peer_id: &[u8; 20]) -> String {
+    let mut encoded = base.as_str().to_string();
+    for (name, bytes) in [("info_hash", info_hash.as_slice()), ("peer_id", peer_id.as_slice())] {
+        let placeholder = format!("{}=", name);
+        if let Some(start) = encoded.find(&placeholder) {
+            let value_start = start + placeholder.len();
+            let value_end = encoded[value_start..]
+                .find('&')
+                .map(|i| value_start + i)
+                .unwrap_or(encoded.len());
+            let percent_encoded: String =
+                bytes.iter().map(|b| format!("%{:02X}", b)).collect();
+            encoded.replace_range(value_start..value_end, &percent_encoded);
+        }
+    }
+    encoded
+}
+
+fn parse_http_response(bytes: &[u8]) -> Result<TrackerResponse, String> {
+    let elements = BencodeElem::from_bytes(bytes).map_err(|e| e.to_string())?;
+    let dict = elements
+        .into_iter()
+        .next()
+        .and_then(|e| match e {
+            BencodeElem::Dictionary(d) => Some(d),
+            _ => None,
+        })
+        .ok_or("tracker response was not a bencoded dictionary")?;
+
+    if let Some(BencodeElem::String(reason)) = dict.get("failure reason") {
+        return Err(format!("tracker failure: {}", reason));
+    }
+
+    let interval = match dict.get("interval") {
+        Some(BencodeElem::Integer(n)) => *n as u64,
+        _ => 1800,
+    };
+
+    let peers = match dict.get("peers") {
+        Some(BencodeElem::Bytes(compact)) => parse_compact_peers(compact),
+        _ => Vec::new(),
+    };
+
+    Ok(TrackerResponse { interval, peers })
+}
+
+/// Decode a compact peer list: 6 bytes per peer (4-byte IPv4 + 2-byte port).
+fn parse_compact_peers(data: &[u8]) -> Vec<SocketAddr> {
+    data.chunks_exact(6)
+        .map(|chunk| {
+            let ip = Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3]);
+            let port = u16::from_be_bytes([chunk[4], chunk[5]]);
+            SocketAddr::V4(SocketAddrV4::new(ip, port))
+        })
+        .collect()
+}
+
+/// Announce to a UDP tracker using the BEP 15 connect/announce handshake.
+async fn announce_udp(request: &AnnounceRequest<'_>) -> Result<TrackerResponse, String> {
+    let url = url::Url::parse(request.url).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("UDP tracker URL has no host")?;
+    let port = url.port().ok_or("UDP tracker URL has no port")?;
+    let addr = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("could not resolve UDP tracker host")?;
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    socket.connect(addr).await.map_err(|e| e.to_string())?;
+
+    let connection_id = udp_connect(&socket).await?;
+    udp_announce(&socket, connection_id, request).await
+}
+
+async fn udp_connect(socket: &UdpSocket) -> Result<u64, String> {
+    let transaction_id: u32 = rand::thread_rng().gen();
+
+    let mut packet = Vec::with_capacity(16);
+    packet.extend_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+    packet.extend_from_slice(&UDP_ACTION_CONNECT.to_be_bytes());
+    packet.extend_from_slice(&transaction_id.to_be_bytes());
+
+    let mut response = [0u8; 16];
+    send_and_receive(socket, &packet, &mut response).await?;
+
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let received_txn = u32::from_be_bytes(response[4..8].try_into().unwrap());
+    if action != UDP_ACTION_CONNECT || received_txn != transaction_id {
+        return Err("unexpected UDP tracker connect response".to_string());
+    }
+    Ok(u64::from_be_bytes(response[8..16].try_into().unwrap()))
+}
+
+async fn udp_announce(
+    socket: &UdpSocket,
+    connection_id: u64,
+    request: &AnnounceRequest<'_>,
+) -> Result<TrackerResponse, String> {
+    let transaction_id: u32 = rand::thread_rng().gen();
+    let key: u32 = rand::thread_rng().gen();
+
+    let mut packet = Vec::with_capacity(98);
+    packet.extend_from_slice(&connection_id.to_be_bytes());
+    packet.extend_from_slice(&UDP_ACTION_ANNOUNCE.to_be_bytes());
+    packet.extend_from_slice(&transaction_id.to_be_bytes());
+    packet.extend_from_slice(&request.info_hash);
+    packet.extend_from_slice(&request.peer_id);
+    packet.extend_from_slice(&request.downloaded.to_be_bytes());
+    packet.extend_from_slice(&request.left.to_be_bytes());
+    packet.extend_from_slice(&request.uploaded.to_be_bytes());
+    packet.extend_from_slice(&request.event.as_udp_code().to_be_bytes());
+    packet.extend_from_slice(&0u32.to_be_bytes()); // IP address: default
+    packet.extend_from_slice(&key.to_be_bytes());
+    packet.extend_from_slice(&(-1i32).to_be_bytes()); // num_want: default
+    packet.extend_from_slice(&request.port.to_be_bytes());
+
+    let mut response = [0u8; 1024];
+    let len = send_and_receive_variable(socket, &packet, &mut response).await?;
+
+    // `len` is the size of a datagram the tracker controls; never slice
+    // past it before checking it's at least as long as the fixed header
+    // we're about to read.
+    if len < 8 {
+        return Err("UDP tracker response too short".to_string());
+    }
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let received_txn = u32::from_be_bytes(response[4..8].try_into().unwrap());
+    if received_txn != transaction_id {
+        return Err("unexpected UDP tracker announce response".to_string());
+    }
+    if action == UDP_ACTION_ERROR {
+        let message = String::from_utf8_lossy(&response[8..len]);
+        return Err(format!("UDP tracker error: {message}"));
+    }
+    if action != UDP_ACTION_ANNOUNCE || len < 20 {
+        return Err("unexpected UDP tracker announce response".to_string());
+    }
+
+    let interval = u32::from_be_bytes(response[8..12].try_into().unwrap()) as u64;
+    let peers = parse_compact_peers(&response[20..len]);
+
+    Ok(TrackerResponse { interval, peers })
+}
+
+async fn send_and_receive(
+    socket: &UdpSocket,
+    packet: &[u8],
+    response: &mut [u8],
+) -> Result<(), String> {
+    socket.send(packet).await.map_err(|e| e.to_string())?;
+    tokio::time::timeout(UDP_TIMEOUT, socket.recv(response))
+        .await
+        .map_err(|_| "UDP tracker request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn send_and_receive_variable(
+    socket: &UdpSocket,
+    packet: &[u8],
+    response: &mut [u8],
+) -> Result<usize, String> {
+    socket.send(packet).await.map_err(|e| e.to_string())?;
+    let len = tokio::time::timeout(UDP_TIMEOUT, socket.recv(response))
+        .await
+        .map_err(|_| "UDP tracker request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(len)
+}
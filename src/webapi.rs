@@ -0,0 +1,297 @@
+//! A qBittorrent-compatible Web API server (a subset of the v2 surface),
+//! so existing qBittorrent remote clients and scripts can control this
+//! client headlessly without change.
+//!
+//! Runs on the backend Tokio runtime alongside the COSMIC event loop; see
+//! [`run`].
+
+use crate::config::AppConfig;
+use crate::torrent_engine::{TorrentEngine, TorrentSummary};
+use axum::extract::{Multipart, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tokio::sync::Mutex;
+
+/// An engine shared between the COSMIC UI and the web API server.
+pub type SharedEngine = Arc<Mutex<TorrentEngine>>;
+
+const SESSION_COOKIE: &str = "SID";
+
+/// State shared across every web API request.
+#[derive(Clone)]
+struct ApiState {
+    engine: SharedEngine,
+    username: String,
+    password: String,
+    sessions: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Start the web API server.
+///
+/// Binds `0.0.0.0:{config.web_ui_port}` and serves until the process
+/// exits or the listener errors. It does not check `web_ui_enabled`
+/// itself; `run_backend` in `main.rs` awaits it on the backend runtime
+/// only when that flag is set.
+pub async fn run(engine: SharedEngine, config: &AppConfig) -> Result<(), String> {
+    let state = ApiState {
+        engine,
+        username: config.web_ui_username.clone(),
+        password: config.web_ui_password.clone(),
+        sessions: Arc::new(Mutex::new(HashSet::new())),
+    };
+
+    let app = Router::new()
+        .route("/api/v2/auth/login", post(login))
+        .route("/api/v2/torrents/info", get(torrents_info))
+        .route("/api/v2/torrents/add", post(torrents_add))
+        .route("/api/v2/torrents/pause", post(torrents_pause))
+        .route("/api/v2/torrents/resume", post(torrents_resume))
+        .route("/api/v2/torrents/delete", post(torrents_delete))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.web_ui_port));
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| e.to_string())?;
+    axum::serve(listener, app).await.map_err(|e| e.to_string())
+}
+
+/// Whether the request carries a cookie matching a live session.
+async fn is_authenticated(headers: &HeaderMap, state: &ApiState) -> bool {
+    let Some(cookie_header) = headers.get(header::COOKIE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let sessions = state.sessions.lock().await;
+    cookie_header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .any(|(name, value)| name == SESSION_COOKIE && sessions.contains(value))
+}
+
+fn forbidden() -> Response {
+    (StatusCode::FORBIDDEN, "Forbidden").into_response()
+}
+
+fn generate_session_token() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+}
+
+/// `POST /api/v2/auth/login`: exchange username/password for a session
+/// cookie, matching the plain-text "Ok."/"Fails." body qBittorrent returns.
+async fn login(State(state): State<ApiState>, Form(form): Form<LoginForm>) -> Response {
+    let password_matches: bool = form
+        .password
+        .as_bytes()
+        .ct_eq(state.password.as_bytes())
+        .into();
+    if form.username != state.username || !password_matches {
+        return (StatusCode::OK, "Fails.").into_response();
+    }
+
+    let token = generate_session_token();
+    state.sessions.lock().await.insert(token.clone());
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly").parse() {
+        headers.insert(header::SET_COOKIE, value);
+    }
+    (headers, "Ok.").into_response()
+}
+
+/// The fields of a torrent that qBittorrent clients expect back from
+/// `torrents/info`, built from [`TorrentSummary`].
+#[derive(Serialize)]
+struct TorrentInfoResponse {
+    hash: String,
+    name: String,
+    size: u64,
+    progress: f32,
+    dlspeed: u64,
+    upspeed: u64,
+    num_seeds: u32,
+    num_leechs: u32,
+    state: String,
+}
+
+impl From<&TorrentSummary> for TorrentInfoResponse {
+    fn from(summary: &TorrentSummary) -> Self {
+        let done = summary.stats.progress >= 1.0;
+        let state = match (summary.paused, done) {
+            (true, true) => "pausedUP",
+            (true, false) => "pausedDL",
+            (false, true) => "uploading",
+            (false, false) => "downloading",
+        };
+
+        Self {
+            hash: summary.info_hash.clone(),
+            name: summary.info.name.clone(),
+            size: summary.info.size,
+            progress: summary.stats.progress,
+            dlspeed: summary.stats.download_rate,
+            upspeed: summary.stats.upload_rate,
+            num_seeds: summary.stats.seeds,
+            num_leechs: summary.stats.peers,
+            state: state.to_string(),
+        }
+    }
+}
+
+/// `GET /api/v2/torrents/info`: the current state of every managed
+/// torrent, read live from the engine.
+async fn torrents_info(State(state): State<ApiState>, headers: HeaderMap) -> Response {
+    if !is_authenticated(&headers, &state).await {
+        return forbidden();
+    }
+
+    let summaries = state.engine.lock().await.list_torrents();
+    let body: Vec<TorrentInfoResponse> = summaries.iter().map(TorrentInfoResponse::from).collect();
+    Json(body).into_response()
+}
+
+/// `POST /api/v2/torrents/add`: accepts a multipart form with an `urls`
+/// text field (one magnet link per line) and/or one or more uploaded
+/// `.torrent` file parts.
+async fn torrents_add(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    if !is_authenticated(&headers, &state).await {
+        return forbidden();
+    }
+
+    let mut added_any = false;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("urls") {
+            let Ok(text) = field.text().await else { continue };
+            for magnet in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                let mut engine = state.engine.lock().await;
+                added_any |= engine.add_magnet(magnet).await.is_ok();
+            }
+        } else {
+            let Ok(bytes) = field.bytes().await else { continue };
+            if let Ok(path) = write_temp_torrent(&bytes) {
+                let mut engine = state.engine.lock().await;
+                added_any |= engine
+                    .add_torrent_file(&path.to_string_lossy())
+                    .await
+                    .is_ok();
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    if added_any {
+        (StatusCode::OK, "Ok.").into_response()
+    } else {
+        (StatusCode::from_u16(415).unwrap(), "Torrent not added.").into_response()
+    }
+}
+
+/// Write an uploaded `.torrent` payload to a scratch file so it can be
+/// parsed with [`TorrentEngine::add_torrent_file`], which reads from a path.
+fn write_temp_torrent(bytes: &[u8]) -> std::io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!(
+        "cosmic-torrent-upload-{}.torrent",
+        generate_session_token()
+    ));
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+#[derive(Deserialize)]
+struct HashesForm {
+    hashes: String,
+}
+
+/// Resolve a qBittorrent-style `hashes` parameter: either the literal
+/// `all`, or a `|`-separated list of info hashes.
+async fn resolve_hashes(raw: &str, engine: &TorrentEngine) -> Vec<String> {
+    if raw == "all" {
+        engine
+            .list_torrents()
+            .into_iter()
+            .map(|summary| summary.info_hash)
+            .collect()
+    } else {
+        raw.split('|').map(str::to_string).collect()
+    }
+}
+
+/// `POST /api/v2/torrents/pause`
+async fn torrents_pause(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Form(form): Form<HashesForm>,
+) -> Response {
+    if !is_authenticated(&headers, &state).await {
+        return forbidden();
+    }
+    let mut engine = state.engine.lock().await;
+    for hash in resolve_hashes(&form.hashes, &engine).await {
+        let _ = engine.pause_torrent(&hash);
+    }
+    (StatusCode::OK, "Ok.").into_response()
+}
+
+/// `POST /api/v2/torrents/resume`
+async fn torrents_resume(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Form(form): Form<HashesForm>,
+) -> Response {
+    if !is_authenticated(&headers, &state).await {
+        return forbidden();
+    }
+    let mut engine = state.engine.lock().await;
+    for hash in resolve_hashes(&form.hashes, &engine).await {
+        let _ = engine.resume_torrent(&hash);
+    }
+    (StatusCode::OK, "Ok.").into_response()
+}
+
+#[derive(Deserialize)]
+struct DeleteForm {
+    hashes: String,
+    #[serde(rename = "deleteFiles", default)]
+    #[allow(dead_code)]
+    delete_files: bool,
+}
+
+/// `POST /api/v2/torrents/delete`
+///
+/// `deleteFiles` is accepted for compatibility but not yet acted on;
+/// `TorrentEngine::remove_torrent` only forgets the torrent, it doesn't
+/// touch downloaded data.
+async fn torrents_delete(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Form(form): Form<DeleteForm>,
+) -> Response {
+    if !is_authenticated(&headers, &state).await {
+        return forbidden();
+    }
+    let mut engine = state.engine.lock().await;
+    for hash in resolve_hashes(&form.hashes, &engine).await {
+        let _ = engine.remove_torrent(&hash);
+    }
+    (StatusCode::OK, "Ok.").into_response()
+}